@@ -1,6 +1,8 @@
 use schnorrkel::{Keypair, MiniSecretKey, signing_context};
 use sp_core::crypto::{Ss58Codec, AccountId32};
+use sp_core::{ecdsa, ed25519, hashing::keccak_256, Pair};
 use hex::{self, FromHex};
+use scale::Encode;
 use clap::Parser;
 
 #[derive(Parser, Debug)]
@@ -12,17 +14,128 @@ struct Args {
     #[clap(long, help = "Coupon secret key 0x...")]
     coupon: String,
 
-    #[clap(long, help = "Receiver address SS58")]
-    receiver: String,
+    #[clap(
+        long,
+        default_value = "sr25519",
+        possible_values = ["sr25519", "ed25519", "ecdsa"],
+        help = "Key scheme of the coupon"
+    )]
+    scheme: String,
+
+    #[clap(long, help = "Receiver address SS58", required_unless_present_any = ["rekey-to", "cancel"])]
+    receiver: Option<String>,
+
+    #[clap(
+        long,
+        help = "Amount for partial redemption or airdrop claim, without it signs full activation"
+    )]
+    amount: Option<u128>,
+
+    #[clap(
+        long,
+        help = "Airdrop id to sign the claim of the airdrop coupon with `--amount`"
+    )]
+    airdrop: Option<u32>,
+
+    #[clap(long, default_value = "0", help = "Index of the coupon leaf in the airdrop")]
+    index: u32,
+
+    #[clap(long, default_value = "0", help = "Coupon nonce for partial redemption")]
+    nonce: u32,
+
+    #[clap(long, help = "Relayer fee for relayed activation")]
+    fee: Option<u128>,
+
+    #[clap(
+        long,
+        help = "Relayer address SS58 for relayed activation, any caller can relay without it"
+    )]
+    relayer: Option<String>,
+
+    #[clap(
+        long,
+        help = "Last block number the activation, redemption or relay signature is valid in"
+    )]
+    deadline: Option<u32>,
+
+    #[clap(
+        long,
+        default_value = "0x0000000000000000000000000000000000000000000000000000000000000000",
+        help = "Chain identifier (genesis hash) set in the contract 0x..."
+    )]
+    chain: String,
+
+    #[clap(
+        long,
+        help = "Coupon id 0x..., required for ECDSA coupons (Ethereum address padded with zeros)"
+    )]
+    coupon_id: Option<String>,
+
+    #[clap(
+        long,
+        help = "New coupon id 0x... to sign rekeying of the coupon instead of activation"
+    )]
+    rekey_to: Option<String>,
+
+    #[clap(long, help = "Sign cancellation of the coupon instead of activation")]
+    cancel: bool,
+
+    #[clap(
+        long,
+        help = "Sign legacy `activate_coupon` payload (receiver address only) without deadline"
+    )]
+    legacy: bool,
 
     #[clap(long, help = "Output only hex signature")]
-    short: bool
+    short: bool,
 }
 
 #[derive(Debug)]
 enum Error {
     ParseSS58(String),
     InvalidSecret,
+    InvalidChain,
+    InvalidCouponId,
+    InvalidNewCoupon,
+    MissingDeadline,
+    MissingCouponId,
+    MissingAmount,
+}
+
+// Version of the signed activation payload
+const PAYLOAD_VERSION: u8 = 1;
+
+// Action of the signed payload, same order as in the contract
+#[derive(Encode)]
+enum PayloadAction {
+    Activation,
+    Redemption,
+    Relay,
+    AirdropClaim,
+    Rekey,
+    Cancellation,
+}
+
+fn parse_hex(value: &str, error: Error) -> Result<[u8; 32], Error> {
+    value
+        .strip_prefix("0x")
+        .and_then(|value| <[u8; 32]>::from_hex(value).ok())
+        .ok_or(error)
+}
+
+fn coupon_public(args: &Args, secret: &[u8; 32]) -> Result<[u8; 32], Error> {
+    match (&args.coupon_id, &*args.scheme) {
+        (Some(coupon_id), _) => parse_hex(coupon_id, Error::InvalidCouponId),
+        (None, "ed25519") => Ok(ed25519::Pair::from_seed(secret).public().into()),
+        (None, "ecdsa") => Err(Error::MissingCouponId),
+        (None, _) => {
+            let coupon = MiniSecretKey::from_bytes(secret).or(Err(Error::InvalidSecret))?;
+            Ok(coupon
+                .expand_to_keypair(MiniSecretKey::ED25519_MODE)
+                .public
+                .to_bytes())
+        }
+    }
 }
 
 fn main() -> Result<(), Error> {
@@ -33,29 +146,147 @@ fn main() -> Result<(), Error> {
         AccountId32::from_ss58check(&*args.contract).or(Err(Error::ParseSS58("contract".to_string())))?;
     let contract_address_context_bytes: &[u8; 32] = contract_address.as_ref();
 
-    // Receiver address
-    let receiver_address =
-        AccountId32::from_ss58check(&*args.receiver).or(Err(Error::ParseSS58("receiver".to_string())))?;
+    // Receiver address, not used for rekeying & cancellation
+    let receiver_address = match &args.receiver {
+        Some(receiver) => {
+            AccountId32::from_ss58check(receiver).or(Err(Error::ParseSS58("receiver".to_string())))?
+        }
+        None => AccountId32::new([0; 32]),
+    };
     let receiver_address_bytes: &[u8; 32] = receiver_address.as_ref();
 
+    // Optional relayer address
+    let relayer_address_bytes = match &args.relayer {
+        Some(relayer) => Some(<[u8; 32]>::from(
+            AccountId32::from_ss58check(relayer).or(Err(Error::ParseSS58("relayer".to_string())))?,
+        )),
+        None => None,
+    };
+
     // Coupon private key (is Charlie account)
     let coupon_hex = args.coupon.strip_prefix("0x").ok_or(Error::InvalidSecret)?;
     let coupon_secret_bytes = <[u8; 32]>::from_hex(&*coupon_hex).or(Err(Error::InvalidSecret))?;
-    let coupon = MiniSecretKey::from_bytes(&coupon_secret_bytes).or(Err(Error::InvalidSecret))?;
+
+    // Domain header of the signed payload: version, action, contract & chain identifier
+    let chain = parse_hex(&args.chain, Error::InvalidChain)?;
+    let domain = |action| (PAYLOAD_VERSION, action, contract_address_context_bytes, chain);
+
+    // Signed payload
+    let payload = match (args.amount, args.fee) {
+        _ if args.cancel => (
+            domain(PayloadAction::Cancellation),
+            coupon_public(&args, &coupon_secret_bytes)?,
+        )
+            .encode(),
+        (amount, _) if args.airdrop.is_some() => (
+            domain(PayloadAction::AirdropClaim),
+            args.airdrop.unwrap_or_default(),
+            args.index,
+            coupon_public(&args, &coupon_secret_bytes)?,
+            amount.ok_or(Error::MissingAmount)?,
+            receiver_address_bytes,
+            args.deadline.ok_or(Error::MissingDeadline)?,
+        )
+            .encode(),
+        _ if args.rekey_to.is_some() => (
+            domain(PayloadAction::Rekey),
+            coupon_public(&args, &coupon_secret_bytes)?,
+            parse_hex(
+                args.rekey_to.as_deref().unwrap_or_default(),
+                Error::InvalidNewCoupon,
+            )?,
+        )
+            .encode(),
+        (Some(amount), _) => (
+            domain(PayloadAction::Redemption),
+            coupon_public(&args, &coupon_secret_bytes)?,
+            receiver_address_bytes,
+            amount,
+            args.nonce,
+            args.deadline.ok_or(Error::MissingDeadline)?,
+        )
+            .encode(),
+        (None, Some(fee)) => (
+            domain(PayloadAction::Relay),
+            coupon_public(&args, &coupon_secret_bytes)?,
+            receiver_address_bytes,
+            fee,
+            relayer_address_bytes,
+            args.deadline.ok_or(Error::MissingDeadline)?,
+        )
+            .encode(),
+        (None, None) if args.legacy => receiver_address_bytes.to_vec(),
+        (None, None) => (
+            domain(PayloadAction::Activation),
+            coupon_public(&args, &coupon_secret_bytes)?,
+            receiver_address_bytes,
+            args.deadline.ok_or(Error::MissingDeadline)?,
+        )
+            .encode(),
+    };
+    // ed25519 & ECDSA sign the contract address followed by the payload
+    let message = [&contract_address_context_bytes[..], &payload].concat();
+    // ECDSA signs keccak256 of the EIP-191 personal message
+    let eth_message =
+        [format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes(), &message].concat();
 
     // Make signature
-    let keypair = Keypair::from(coupon.expand(MiniSecretKey::ED25519_MODE));
-    let context = signing_context(contract_address_context_bytes);
-    let signature = keypair.sign(context.bytes(receiver_address_bytes));
-    let hex_signature = hex::encode(signature.to_bytes());
+    let signature = match &*args.scheme {
+        "ed25519" => {
+            let signature = ed25519::Pair::from_seed(&coupon_secret_bytes).sign(&message);
+            AsRef::<[u8]>::as_ref(&signature).to_vec()
+        }
+        "ecdsa" => {
+            let signature =
+                ecdsa::Pair::from_seed(&coupon_secret_bytes).sign_prehashed(&keccak_256(&eth_message));
+            // recovery id isn't passed to the contract
+            AsRef::<[u8]>::as_ref(&signature)[..64].to_vec()
+        }
+        _ => {
+            let coupon = MiniSecretKey::from_bytes(&coupon_secret_bytes).or(Err(Error::InvalidSecret))?;
+            let keypair = Keypair::from(coupon.expand(MiniSecretKey::ED25519_MODE));
+            let context = signing_context(contract_address_context_bytes);
+
+            keypair.sign(context.bytes(&payload)).to_bytes().to_vec()
+        }
+    };
+    let hex_signature = hex::encode(signature);
 
     if args.short {
         println!("0x{:}", hex_signature);
     } else {
         println!("---------------------------------------");
         println!("Contract Address: {:}", args.contract);
-        println!("Payout Receiver: {:}", args.receiver);
+        println!("Payout Receiver: {:}", args.receiver.as_deref().unwrap_or("none"));
         println!("Coupon Secret Key: {:}", args.coupon);
+        println!("Coupon Key Scheme: {:}", args.scheme);
+
+        if args.cancel {
+            println!("Cancellation Chain: {:}", args.chain);
+        } else if let Some(new_coupon) = &args.rekey_to {
+            println!("New Coupon: {:}", new_coupon);
+            println!("Chain: {:}", args.chain);
+        } else if let Some(airdrop) = args.airdrop {
+            println!("Airdrop: {:}", airdrop);
+            println!("Coupon Index: {:}", args.index);
+            println!("Claim Amount: {:}", args.amount.unwrap_or_default());
+            println!("Deadline Block: {:}", args.deadline.unwrap_or_default());
+            println!("Chain: {:}", args.chain);
+        } else if let Some(amount) = args.amount {
+            println!("Redeem Amount: {:}", amount);
+            println!("Coupon Nonce: {:}", args.nonce);
+            println!("Deadline Block: {:}", args.deadline.unwrap_or_default());
+            println!("Chain: {:}", args.chain);
+        } else if let Some(fee) = args.fee {
+            println!("Relayer Fee: {:}", fee);
+            println!("Relayer: {:}", args.relayer.as_deref().unwrap_or("any"));
+            println!("Deadline Block: {:}", args.deadline.unwrap_or_default());
+            println!("Chain: {:}", args.chain);
+        } else if !args.legacy {
+            println!("Deadline Block: {:}", args.deadline.unwrap_or_default());
+            println!("Chain: {:}", args.chain);
+        }
+
         println!("Signature: 0x{:}", hex_signature);
     }
 
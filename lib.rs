@@ -7,21 +7,449 @@
 #[ink::contract]
 mod ocex {
     use schnorrkel::{signing_context, PublicKey, Signature};
-    use ink::storage::Mapping;
+    use ink::env::call::utils::{ArgsList, EmptyArgumentList};
+    use ink::env::call::{build_call, Call, CallParams, ExecutionInput, Selector};
+    use ink::env::hash::{Blake2x256, HashOutput, Keccak256};
+    use ink::prelude::{format, string::String, vec::Vec};
+    use ink::storage::traits::{Packed, StorageKey};
+    use ink::storage::{Lazy, Mapping};
+    use scale::Encode;
 
     type ReceiverAddress = AccountId;
     type CouponId = AccountId;
+    // PSP22 token contract address, coupons without token are paid in native currency
+    type TokenId = AccountId;
+    type CampaignId = u32;
+    type AirdropId = u32;
+    type MerkleHash = <Blake2x256 as HashOutput>::Type;
 
     // Coupons list arguments of request/response
-    type OptCoupons = [Option<CouponId>; 5];
+    type Coupons = Vec<CouponId>;
+
+    // Maximum coupons in one batch request, if not configured by the admin
+    const DEFAULT_MAX_BATCH_SIZE: u32 = 100;
+
+    // Version of the storage layout, bump it when stored data
+    // needs conversion and add the conversion step to `migrate`
+    const STORAGE_VERSION: u32 = 1;
+
+    // Version of the signed activation payload
+    const PAYLOAD_VERSION: u8 = 1;
+
+    // Maximum receivers a coupon can be bound to
+    const MAX_BOUND_RECEIVERS: usize = 10;
 
     /// Result for inserted and declined coupons
     /// when balance is not enough to guarantee payout
     #[derive(Debug, Default, PartialEq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     pub struct CouponsResult {
-        accepted: OptCoupons,
-        declined: OptCoupons,
+        accepted: Coupons,
+        declined: Coupons,
+    }
+
+    /// Result for inserted and declined coupons with individual amounts,
+    /// every declined coupon comes with the decline reason
+    #[derive(Debug, Default, PartialEq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct CouponsAmountsResult {
+        accepted: Coupons,
+        declined: Vec<(CouponId, Error)>,
+    }
+
+    /// Campaign groups coupons under a label with its own budget.
+    /// Coupons are issued until the campaign `ends` and can be redeemed
+    /// only between optional `starts` and `ends` timestamps.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Campaign {
+        label: String,
+        token: Option<TokenId>,
+        // Cap for the total amount of issued coupons
+        budget: Balance,
+        starts: Option<Timestamp>,
+        ends: Option<Timestamp>,
+        // Total amount of issued coupons
+        issued: Balance,
+        // Total amount paid out to receivers
+        redeemed: Balance,
+        // Total amount of burned coupons
+        burned: Balance,
+        // Outstanding amount reserved for the campaign coupons
+        reserved: Balance,
+        // Count of issued coupons
+        coupons: u32,
+        // Count of coupons processed by `close_campaign`
+        closed_coupons: u32,
+        closed: bool,
+    }
+
+    /// Campaign total the released reservation is moved to
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum CampaignRelease {
+        /// Paid out to receivers
+        Redeemed,
+        /// Burned without redemption
+        Burned,
+    }
+
+    /// Airdrop registers a whole batch of coupons by the Merkle `root` with the total reservation.
+    /// Leaves are `blake2x256` hashes of SCALE encoded `(index, coupon, amount)`,
+    /// parent node is the hash of sorted pair of children.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Airdrop {
+        root: MerkleHash,
+        token: Option<TokenId>,
+        // Claims are accepted until the timestamp
+        ends: Option<Timestamp>,
+        // Leftover reserved amount for claims
+        reserved: Balance,
+        // Total amount paid out to receivers
+        claimed: Balance,
+        closed: bool,
+    }
+
+    /// Coupon status reported by `coupon_info`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum CouponStatus {
+        /// Coupon was never registered
+        NotRegistered,
+        /// Coupon can be redeemed
+        Active,
+        /// Coupon is registered, but the contract balance isn't enough for payout
+        Underfunded,
+        /// Coupon campaign is closed or out of the redemption period
+        Inactive,
+        /// Coupon expiry has passed
+        Expired,
+        /// Coupon balance is paid out to receivers
+        Redeemed,
+        /// Coupon is revoked without redemption
+        Revoked,
+        /// Expired coupon is reclaimed by the `Revoker`
+        Reclaimed,
+        /// Coupon is moved to a new key by the holder
+        Rekeyed,
+        /// Coupon is declined by the holder without redemption
+        Cancelled,
+    }
+
+    impl From<CouponState> for CouponStatus {
+        fn from(state: CouponState) -> Self {
+            match state {
+                CouponState::Redeemed => CouponStatus::Redeemed,
+                CouponState::Revoked => CouponStatus::Revoked,
+                CouponState::Expired => CouponStatus::Reclaimed,
+                CouponState::Rekeyed => CouponStatus::Rekeyed,
+                CouponState::Cancelled => CouponStatus::Cancelled,
+            }
+        }
+    }
+
+    /// Counts of registered coupons by the indexed status
+    #[derive(Debug, Clone, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct CouponCounts {
+        total: u32,
+        // Coupons aren't redeemed, revoked or reclaimed yet, expired ones are counted until reclaimed
+        active: u32,
+        redeemed: u32,
+        revoked: u32,
+        // Reclaimed expired coupons
+        reclaimed: u32,
+        // Old keys of rekeyed coupons
+        rekeyed: u32,
+        // Coupons declined by holders
+        cancelled: u32,
+        // Finished coupons removed from status lists by `prune_coupons`
+        pruned: u32,
+    }
+
+    /// Terminal state of a coupon, coupons in any state can't be activated
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum CouponState {
+        /// Coupon balance is paid out to receivers.
+        /// Indexes start from 1 to decode the legacy `true` burned flag as redeemed
+        #[codec(index = 1)]
+        Redeemed,
+        /// Coupon is burned by the `Revoker`
+        #[codec(index = 2)]
+        Revoked,
+        /// Expired coupon is reclaimed by the `Revoker`
+        #[codec(index = 3)]
+        Expired,
+        /// Coupon is moved to a new key by the holder, the old key is nullified
+        #[codec(index = 4)]
+        Rekeyed,
+        /// Coupon is declined by the holder, the reservation is released
+        #[codec(index = 5)]
+        Cancelled,
+    }
+
+    /// Coupon issuance record
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Issuance {
+        issuer: AccountId,
+        timestamp: Timestamp,
+    }
+
+    /// Coupon redemption receipt of the last redemption with its receiver,
+    /// paid amount & time, and the total redeemed amount of the coupon
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Receipt {
+        receiver: ReceiverAddress,
+        // Amount paid to the receiver of the last redemption
+        amount: Balance,
+        // Relayer fee of the last redemption paid from the coupon
+        fee: Balance,
+        // Total redeemed amount of all redemptions including relayer fees
+        total: Balance,
+        block: BlockNumber,
+        timestamp: Timestamp,
+    }
+
+    /// Action of the signed payload, signature of one action can't be used for another
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PayloadAction {
+        Activation,
+        Redemption,
+        Relay,
+        AirdropClaim,
+        Rekey,
+        Cancellation,
+    }
+
+    /// Domain header of every signed payload: version, signed action, contract
+    /// and chain identifier (genesis hash) set by the `Admin` with `set_chain_id`
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct PayloadDomain {
+        version: u8,
+        action: PayloadAction,
+        contract: AccountId,
+        chain: Hash,
+    }
+
+    /// Versioned payload of `activate_coupon` signatures, SCALE encoded and signed by the coupon key
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct ActivationPayload {
+        domain: PayloadDomain,
+        coupon: CouponId,
+        receiver: ReceiverAddress,
+        // Last block the signature is valid in
+        deadline: BlockNumber,
+    }
+
+    /// Versioned payload of `redeem_coupon` signatures, SCALE encoded and signed by the coupon key
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct RedemptionPayload {
+        domain: PayloadDomain,
+        coupon: CouponId,
+        receiver: ReceiverAddress,
+        amount: Balance,
+        // Current coupon nonce
+        nonce: u32,
+        // Last block the signature is valid in
+        deadline: BlockNumber,
+    }
+
+    /// Versioned payload of `relay_activate_coupon` signatures, SCALE encoded and signed by the coupon key
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct RelayPayload {
+        domain: PayloadDomain,
+        coupon: CouponId,
+        receiver: ReceiverAddress,
+        fee: Balance,
+        // Only this account can relay the activation if set
+        relayer: Option<AccountId>,
+        // Last block the signature is valid in
+        deadline: BlockNumber,
+    }
+
+    /// Versioned payload of `rekey_coupon` signatures, SCALE encoded and signed by the old coupon key
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct RekeyPayload {
+        domain: PayloadDomain,
+        coupon: CouponId,
+        new_coupon: CouponId,
+    }
+
+    /// Versioned payload of `cancel_coupon` signatures, SCALE encoded and signed by the coupon key
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct CancellationPayload {
+        domain: PayloadDomain,
+        coupon: CouponId,
+    }
+
+    /// Versioned payload of `claim_airdrop` signatures, SCALE encoded and signed by the coupon key
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct AirdropClaimPayload {
+        domain: PayloadDomain,
+        airdrop: AirdropId,
+        index: u32,
+        coupon: CouponId,
+        amount: Balance,
+        receiver: ReceiverAddress,
+        // Last block the signature is valid in
+        deadline: BlockNumber,
+    }
+
+    /// Key scheme of the coupon signatures
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum KeyScheme {
+        /// Schnorrkel `sr25519` with `contract id` signing context
+        #[default]
+        Sr25519,
+        /// `ed25519` of `contract id` followed by the payload
+        Ed25519,
+        /// `secp256k1` ECDSA of `keccak256` hash of the EIP-191 personal message
+        /// (`"\x19Ethereum Signed Message:\n"`, message length) of `contract id` followed by the payload,
+        /// the coupon is the Ethereum address of the key padded with zeros to 32 bytes
+        Ecdsa,
+    }
+
+    /// Limits of the fee paid to relayers from the coupon amount,
+    /// relayers can't take any fee until the owner sets the limits
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct FeeLimits {
+        // Maximum fee amount
+        max_fee: Balance,
+        // Maximum fee share of the coupon amount in basis points
+        max_fee_bps: u16,
+    }
+
+    /// Reserved and actual balances of native currency or PSP22 token returned by `reconcile`
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Reconciliation {
+        token: Option<TokenId>,
+        reserved: Balance,
+        balance: Balance,
+        // Reserved amount not covered by the balance
+        shortfall: Balance,
+        // Invariant `reserved <= balance` holds
+        solvent: bool,
+        // Recomputed outstanding amount of coupons, vestings & airdrops up to the cursor
+        outstanding: Balance,
+        // Cursor of the next page, none if it's the last page
+        cursor: Option<u32>,
+        // Count of coupons paged before airdrops, taken on the first page
+        coupons: u32,
+        // Recomputed outstanding amount equals `reserved`, reported on the last page
+        consistent: Option<bool>,
+    }
+
+    /// Full coupon info returned by `coupon_info`
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct CouponInfo {
+        status: CouponStatus,
+        // Rest of the coupon balance
+        amount: Balance,
+        token: Option<TokenId>,
+        expires: Option<Expiry>,
+        campaign: Option<CampaignId>,
+        issuer: Option<AccountId>,
+        issued_at: Option<Timestamp>,
+        receiver: Option<ReceiverAddress>,
+        redeemed_at: Option<Timestamp>,
+        // Receivers the coupon is bound to, empty for bearer coupons
+        receivers: Vec<ReceiverAddress>,
+    }
+
+    /// Vesting schedule of the coupon set on registration, durations are in milliseconds
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct VestingTerms {
+        // Nothing is vested until the cliff
+        cliff: Timestamp,
+        // Amount is vested linearly until the end of the duration
+        duration: Timestamp,
+        // Owner can cancel the unvested part
+        revocable: bool,
+    }
+
+    /// Vesting position of the receiver started by activation of the vesting coupon
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Vesting {
+        receiver: ReceiverAddress,
+        token: Option<TokenId>,
+        total: Balance,
+        claimed: Balance,
+        start: Timestamp,
+        terms: VestingTerms,
+        // Vesting stops at the time of cancellation
+        cancelled: Option<Timestamp>,
+        // Campaign of the vesting coupon, its totals are updated on claims and cancellation
+        campaign: Option<CampaignId>,
+    }
+
+    /// Multi-use coupon pays the fixed `amount` once per receiver up to `max_redemptions` times
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct MultiUse {
+        amount: Balance,
+        max_redemptions: u32,
+        redemptions: u32,
+    }
+
+    /// Optional coupon expiry, the coupon can't be activated
+    /// starting from the declared block timestamp or block number
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum Expiry {
+        /// Block timestamp in milliseconds
+        Timestamp(Timestamp),
+        /// Block number
+        BlockNumber(BlockNumber),
     }
 
     impl From<scale::Error> for CouponsResult {
@@ -30,6 +458,111 @@ mod ocex {
         }
     }
 
+    /// Access roles, the contract owner has all roles
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub enum Role {
+        /// Grants and revokes roles
+        Admin,
+        /// Adds new coupons
+        Issuer,
+        /// Burns registered coupons
+        Revoker,
+        /// Withdraws spare funds to the owner's wallet
+        Treasurer,
+        /// Pauses issuance and redemption of coupons
+        Guardian,
+    }
+
+    /// Errors of PSP22 token contract calls
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum PSP22Error {
+        Custom(String),
+        InsufficientBalance,
+        InsufficientAllowance,
+        ZeroRecipientAddress,
+        ZeroSenderAddress,
+        SafeTransferCheckFailed(String),
+    }
+
+    // Result of the cross-contract call decoded as `T`
+    type CallResult<T> = Result<ink::MessageResult<T>, ink::env::Error>;
+
+    /// Calls of PSP22 token contracts made by the contract
+    trait Psp22 {
+        /// `PSP22::transfer` of the contract tokens to the receiver
+        fn transfer(token: TokenId, to: AccountId, amount: Balance) -> CallResult<Result<(), PSP22Error>>;
+
+        /// `PSP22::balance_of` the owner
+        fn balance_of(token: TokenId, owner: AccountId) -> CallResult<Balance>;
+    }
+
+    // Arguments of `PSP22::transfer`: receiver, amount & data
+    type TransferArgs = ArgsList<Vec<u8>, ArgsList<Balance, ArgsList<AccountId, EmptyArgumentList>>>;
+    // Arguments of `PSP22::balance_of`: owner
+    type BalanceOfArgs = ArgsList<AccountId, EmptyArgumentList>;
+
+    /// Cross-contract calls of PSP22 token contracts
+    struct Psp22Calls;
+
+    impl Psp22Calls {
+        fn transfer_params(
+            token: TokenId,
+            to: AccountId,
+            amount: Balance,
+        ) -> CallParams<Environment, Call<Environment>, TransferArgs, Result<(), PSP22Error>> {
+            build_call::<Environment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("PSP22::transfer")))
+                        .push_arg(to)
+                        .push_arg(amount)
+                        .push_arg(Vec::<u8>::new()),
+                )
+                .returns::<Result<(), PSP22Error>>()
+                .params()
+        }
+
+        fn balance_of_params(
+            token: TokenId,
+            owner: AccountId,
+        ) -> CallParams<Environment, Call<Environment>, BalanceOfArgs, Balance> {
+            build_call::<Environment>()
+                .call(token)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ink::selector_bytes!("PSP22::balance_of")))
+                        .push_arg(owner),
+                )
+                .returns::<Balance>()
+                .params()
+        }
+    }
+
+    impl Psp22 for Psp22Calls {
+        fn transfer(token: TokenId, to: AccountId, amount: Balance) -> CallResult<Result<(), PSP22Error>> {
+            Self::transfer_params(token, to, amount).try_invoke()
+        }
+
+        fn balance_of(token: TokenId, owner: AccountId) -> CallResult<Balance> {
+            Self::balance_of_params(token, owner).try_invoke()
+        }
+    }
+
+    /// PSP22 calls made by the contract, the off-chain environment
+    /// can't call contracts, so unit tests provide their own calls
+    trait TokenCalls {
+        type Tokens: Psp22;
+    }
+
+    #[cfg(not(test))]
+    impl TokenCalls for Ocex {
+        type Tokens = Psp22Calls;
+    }
+
     #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
     /// Error types
@@ -47,113 +580,722 @@ mod ocex {
         VerifySignatureFailed,
         /// Coupon already exists
         CouponAlreadyExists,
-        /// Coupon already burned
+        /// Deprecated, replaced by `CouponAlreadyRedeemed` and `CouponRevoked`.
+        /// Kept to keep indexes of the following errors
         CouponAlreadyBurned,
         /// Coupon not found
         CouponNotFound,
         /// Transfer Errors
         TransferFailed,
+        /// Coupon expiry has passed
+        CouponExpired,
+        /// Redeemed amount must be greater than zero
+        ZeroRedeemAmount,
+        /// Caller doesn't have the role
+        MissingRole(Role),
+        /// Roles of the owner can't be revoked
+        OwnerRoleNotRevocable,
+        /// Issuance or redemption of coupons is paused
+        Paused,
+        /// Contract code upgrade failed
+        UpgradeFailed,
+        /// Storage is already migrated to the current version
+        AlreadyMigrated,
+        /// Coupons batch exceeds the maximum batch size
+        BatchTooLarge,
+        /// Maximum batch size must be greater than zero
+        InvalidBatchSize,
+        /// Campaign not found
+        CampaignNotFound,
+        /// Campaign is closed or ended
+        CampaignClosed,
+        /// Campaign redemption period isn't started or is over
+        CampaignNotActive,
+        /// Issued coupons exceed the campaign budget
+        CampaignBudgetExceeded,
+        /// Campaign budget must be greater than zero and its period must start before the end
+        InvalidCampaignTerms,
+        /// Airdrop not found
+        AirdropNotFound,
+        /// Airdrop is closed or ended
+        AirdropClosed,
+        /// Airdrop coupon is already claimed
+        CouponAlreadyClaimed,
+        /// Merkle proof doesn't match the airdrop root
+        InvalidMerkleProof,
+        /// Coupon balance is already paid out
+        CouponAlreadyRedeemed,
+        /// Coupon is revoked by the `Revoker`
+        CouponRevoked,
+        /// Arithmetic overflow or underflow of reserved balances
+        ArithmeticOverflow,
+        /// Coupons aren't listed by the status
+        StatusNotIndexed,
+        /// Relayer fee exceeds the fee limits
+        RelayerFeeTooHigh,
+        /// Caller isn't the relayer signed for the coupon
+        InvalidRelayer,
+        /// Fee share is more than 100%
+        InvalidFeeLimits,
+        /// Signature deadline block has passed
+        SignatureExpired,
+        /// Legacy signatures without deadline aren't accepted anymore
+        LegacySignatureDisabled,
+        /// Coupon isn't bound to the receiver
+        ReceiverNotAllowed,
+        /// Bound coupon needs from 1 to 10 receivers
+        InvalidReceiversCount,
+        /// Multi-use coupons can't be redeemed partially
+        MultiUseCoupon,
+        /// Multi-use coupon amount and maximum redemptions must be greater than zero
+        InvalidMultiUseTerms,
+        /// Vesting coupons can be activated only by `activate_coupon`
+        VestingCoupon,
+        /// Vesting position of the coupon isn't found
+        VestingNotFound,
+        /// Caller isn't the receiver of the vesting position
+        NotVestingReceiver,
+        /// Nothing is vested to claim yet
+        NothingToClaim,
+        /// Vesting isn't revocable or already cancelled
+        VestingNotRevocable,
+        /// Coupon token differs from the campaign token
+        CampaignTokenMismatch,
+        /// Vesting cliff is longer than the duration
+        InvalidVestingTerms,
+        /// Coupon is moved to a new key
+        CouponRekeyed,
+        /// Coupon is cancelled by the holder
+        CouponCancelled,
+    }
+
+    /// Coupon registered and its payout reserved
+    #[ink(event)]
+    pub struct CouponAdded {
+        #[ink(topic)]
+        coupon: CouponId,
+        amount: Balance,
+        token: Option<TokenId>,
+    }
+
+    /// Coupon redeemed and its amount transferred to the receiver
+    #[ink(event)]
+    pub struct CouponActivated {
+        #[ink(topic)]
+        coupon: CouponId,
+        #[ink(topic)]
+        receiver: ReceiverAddress,
+        amount: Balance,
+    }
+
+    /// Part of the coupon balance redeemed and transferred to the receiver
+    #[ink(event)]
+    pub struct CouponRedeemed {
+        #[ink(topic)]
+        coupon: CouponId,
+        #[ink(topic)]
+        receiver: ReceiverAddress,
+        amount: Balance,
+        rest_amount: Balance,
+    }
+
+    /// Coupon burned by the owner and its reservation released
+    #[ink(event)]
+    pub struct CouponBurned {
+        #[ink(topic)]
+        coupon: CouponId,
+        amount: Balance,
+    }
+
+    /// Expired coupon reclaimed by the owner and its reservation released
+    #[ink(event)]
+    pub struct CouponExpired {
+        #[ink(topic)]
+        coupon: CouponId,
+        amount: Balance,
+    }
+
+    /// Spare (not reserved) funds transferred to the owner
+    #[ink(event)]
+    pub struct FundsWithdrawn {
+        #[ink(topic)]
+        receiver: AccountId,
+        amount: Balance,
+        token: Option<TokenId>,
+    }
+
+    /// Role granted to the account
+    #[ink(event)]
+    pub struct RoleGranted {
+        #[ink(topic)]
+        account: AccountId,
+        role: Role,
+    }
+
+    /// Role revoked from the account
+    #[ink(event)]
+    pub struct RoleRevoked {
+        #[ink(topic)]
+        account: AccountId,
+        role: Role,
+    }
+
+    /// Issuance or redemption of coupons paused or resumed
+    #[ink(event)]
+    pub struct PauseChanged {
+        #[ink(topic)]
+        guardian: AccountId,
+        issuance: bool,
+        redemption: bool,
+    }
+
+    /// Contract code replaced with the new code hash
+    #[ink(event)]
+    pub struct CodeUpgraded {
+        #[ink(topic)]
+        code_hash: [u8; 32],
+    }
+
+    /// Storage migrated to the new layout version
+    #[ink(event)]
+    pub struct StorageMigrated {
+        from: u32,
+        to: u32,
+    }
+
+    /// Campaign created
+    #[ink(event)]
+    pub struct CampaignCreated {
+        #[ink(topic)]
+        campaign: CampaignId,
+        label: String,
+        budget: Balance,
+    }
+
+    /// Campaign closed, its unredeemed coupons are burned
+    #[ink(event)]
+    pub struct CampaignClosed {
+        #[ink(topic)]
+        campaign: CampaignId,
+    }
+
+    /// Airdrop coupons registered by the Merkle root
+    #[ink(event)]
+    pub struct AirdropAdded {
+        #[ink(topic)]
+        airdrop: AirdropId,
+        root: MerkleHash,
+        amount: Balance,
+    }
+
+    /// Airdrop coupon claimed and its amount transferred to the receiver
+    #[ink(event)]
+    pub struct AirdropClaimed {
+        #[ink(topic)]
+        airdrop: AirdropId,
+        #[ink(topic)]
+        coupon: CouponId,
+        #[ink(topic)]
+        receiver: ReceiverAddress,
+        amount: Balance,
+    }
+
+    /// Airdrop closed and its leftover reservation released
+    #[ink(event)]
+    pub struct AirdropClosed {
+        #[ink(topic)]
+        airdrop: AirdropId,
+        amount: Balance,
+    }
+
+    /// Vested amount claimed by the receiver
+    #[ink(event)]
+    pub struct VestedClaimed {
+        #[ink(topic)]
+        coupon: CouponId,
+        #[ink(topic)]
+        receiver: ReceiverAddress,
+        amount: Balance,
+    }
+
+    /// Vesting cancelled by the owner, the unvested amount is released
+    #[ink(event)]
+    pub struct VestingCancelled {
+        #[ink(topic)]
+        coupon: CouponId,
+        amount: Balance,
+    }
+
+    /// Coupon moved to a new key by the holder
+    #[ink(event)]
+    pub struct CouponRekeyed {
+        #[ink(topic)]
+        coupon: CouponId,
+        #[ink(topic)]
+        new_coupon: CouponId,
+        amount: Balance,
+    }
+
+    /// Coupon declined by the holder, the reservation is released
+    #[ink(event)]
+    pub struct CouponCancelled {
+        #[ink(topic)]
+        coupon: CouponId,
+        amount: Balance,
+    }
+
+    /// Relayer fee paid from the coupon amount
+    #[ink(event)]
+    pub struct RelayerFeePaid {
+        #[ink(topic)]
+        coupon: CouponId,
+        #[ink(topic)]
+        relayer: AccountId,
+        fee: Balance,
+    }
+
+    /// Contract ownership moved to another account
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        previous: AccountId,
+        #[ink(topic)]
+        owner: AccountId,
     }
 
     #[ink(storage)]
     pub struct Ocex {
         // Coupons are addresses with tokens balances
         coupons: Mapping<CouponId, Balance>,
-        // Burned coupons after activation
-        burned: Mapping<CouponId, bool>,
+        // Terminal states of coupons after redemption, revocation or expiry
+        burned: Mapping<CouponId, CouponState>,
+        // Optional coupons expiry
+        expires: Mapping<CouponId, Expiry>,
+        // Coupons nonces, incremented with every partial redemption
+        nonces: Mapping<CouponId, u32>,
+        // PSP22 tokens of coupons paid in tokens
+        tokens: Mapping<CouponId, TokenId>,
         // Smart-contract owner by default is the contract publisher
         owner: AccountId,
+        // Roles granted to accounts in addition to the owner
+        roles: Mapping<(Role, AccountId), ()>,
         // Reserved balance for coupons payout
         reserved: Balance,
+        // Reserved PSP22 tokens balances for coupons payout
+        reserved_tokens: Mapping<TokenId, Balance>,
+        // Emergency stop of coupons issuance
+        issuance_paused: Lazy<bool>,
+        // Emergency stop of coupons redemption
+        redemption_paused: Lazy<bool>,
+        // Storage layout version, missing in version 0. New fields should be
+        // `Mapping` or `Lazy` to keep the root of older layouts decodable
+        version: Lazy<u32>,
+        // Maximum coupons in one batch request
+        max_batch_size: Lazy<u32>,
+        // Campaigns by id
+        campaigns: Mapping<CampaignId, Campaign>,
+        // Count of created campaigns, the next campaign id
+        campaigns_count: Lazy<CampaignId>,
+        // Campaign of the coupon issued into a campaign
+        coupon_campaigns: Mapping<CouponId, CampaignId>,
+        // Coupons of the campaign by index
+        campaign_coupons: Mapping<(CampaignId, u32), CouponId>,
+        // Airdrops by id
+        airdrops: Mapping<AirdropId, Airdrop>,
+        // Count of registered airdrops, the next airdrop id
+        airdrops_count: Lazy<AirdropId>,
+        // Claimed bitmap of airdrops coupons, 128 leaves per word
+        airdrop_claims: Mapping<(AirdropId, u32), u128>,
+        // Issuer & time of coupons issuance
+        issuances: Mapping<CouponId, Issuance>,
+        // Receipts of redeemed coupons
+        receipts: Mapping<CouponId, Receipt>,
+        // Count of redemptions paid to the receiver
+        receiver_redemptions: Mapping<ReceiverAddress, u32>,
+        // Registered coupons in the insertion order
+        coupons_index: Mapping<u32, CouponId>,
+        // Count of registered coupons, the next insertion index
+        coupons_total: Lazy<u32>,
+        // Coupons by the indexed status
+        // (`Active`, `Redeemed`, `Revoked`, `Reclaimed`, `Rekeyed` or `Cancelled`)
+        status_coupons: Mapping<(CouponStatus, u32), CouponId>,
+        // Position of the coupon in its status list
+        status_positions: Mapping<CouponId, u32>,
+        // Count of coupons in the status list
+        status_counts: Mapping<CouponStatus, u32>,
+        // Count of pruned coupons
+        pruned_total: Lazy<u32>,
+        // Limits of the fee paid to relayers
+        relayer_fee_limits: Lazy<FeeLimits>,
+        // Key schemes of coupons, `sr25519` coupons aren't stored
+        schemes: Mapping<CouponId, KeyScheme>,
+        // Chain identifier of the signed activation payload
+        chain_id: Lazy<Hash>,
+        // Legacy activation signatures are accepted, enabled by default
+        legacy_signatures: Lazy<bool>,
+        // Receivers allowlist of bound coupons, bearer coupons aren't stored
+        bound_receivers: Mapping<CouponId, Vec<ReceiverAddress>>,
+        // Redemptions of multi-use coupons
+        multi_use: Mapping<CouponId, MultiUse>,
+        // Receivers paid by multi-use coupons
+        multi_use_claims: Mapping<(CouponId, ReceiverAddress), ()>,
+        // Vesting schedules of vesting coupons
+        vesting_terms: Mapping<CouponId, VestingTerms>,
+        // Vesting positions of activated vesting coupons
+        vestings: Mapping<CouponId, Vesting>,
+        // New keys of rekeyed coupons
+        rekeys: Mapping<CouponId, CouponId>,
     }
 
     impl Ocex {
         /// You can set a contract owner while deploying the contract
         #[ink(constructor)]
         pub fn new(owner: AccountId) -> Self {
-            Self {
+            let mut contract = Self {
                 coupons: Mapping::new(),
                 burned: Mapping::new(),
+                expires: Mapping::new(),
+                nonces: Mapping::new(),
+                tokens: Mapping::new(),
                 reserved: 0,
+                reserved_tokens: Mapping::new(),
+                roles: Mapping::new(),
+                issuance_paused: Lazy::new(),
+                redemption_paused: Lazy::new(),
+                version: Lazy::new(),
+                max_batch_size: Lazy::new(),
+                campaigns: Mapping::new(),
+                campaigns_count: Lazy::new(),
+                coupon_campaigns: Mapping::new(),
+                campaign_coupons: Mapping::new(),
+                airdrops: Mapping::new(),
+                airdrops_count: Lazy::new(),
+                airdrop_claims: Mapping::new(),
+                issuances: Mapping::new(),
+                receipts: Mapping::new(),
+                receiver_redemptions: Mapping::new(),
+                coupons_index: Mapping::new(),
+                coupons_total: Lazy::new(),
+                status_coupons: Mapping::new(),
+                status_positions: Mapping::new(),
+                status_counts: Mapping::new(),
+                pruned_total: Lazy::new(),
+                relayer_fee_limits: Lazy::new(),
+                schemes: Mapping::new(),
+                chain_id: Lazy::new(),
+                legacy_signatures: Lazy::new(),
+                bound_receivers: Mapping::new(),
+                multi_use: Mapping::new(),
+                multi_use_claims: Mapping::new(),
+                vesting_terms: Mapping::new(),
+                vestings: Mapping::new(),
+                rekeys: Mapping::new(),
                 owner,
-            }
+            };
+
+            contract.version.set(&STORAGE_VERSION);
+            contract
         }
 
         /// Owner is the contract publisher by default
         #[ink(constructor)]
         pub fn default() -> Self {
-            Self {
+            let mut contract = Self {
                 owner: Self::env().caller(),
                 coupons: Mapping::new(),
                 burned: Mapping::new(),
+                expires: Mapping::new(),
+                nonces: Mapping::new(),
+                tokens: Mapping::new(),
                 reserved: 0,
-            }
+                reserved_tokens: Mapping::new(),
+                roles: Mapping::new(),
+                issuance_paused: Lazy::new(),
+                redemption_paused: Lazy::new(),
+                version: Lazy::new(),
+                max_batch_size: Lazy::new(),
+                campaigns: Mapping::new(),
+                campaigns_count: Lazy::new(),
+                coupon_campaigns: Mapping::new(),
+                campaign_coupons: Mapping::new(),
+                airdrops: Mapping::new(),
+                airdrops_count: Lazy::new(),
+                airdrop_claims: Mapping::new(),
+                issuances: Mapping::new(),
+                receipts: Mapping::new(),
+                receiver_redemptions: Mapping::new(),
+                coupons_index: Mapping::new(),
+                coupons_total: Lazy::new(),
+                status_coupons: Mapping::new(),
+                status_positions: Mapping::new(),
+                status_counts: Mapping::new(),
+                pruned_total: Lazy::new(),
+                relayer_fee_limits: Lazy::new(),
+                schemes: Mapping::new(),
+                chain_id: Lazy::new(),
+                legacy_signatures: Lazy::new(),
+                bound_receivers: Mapping::new(),
+                multi_use: Mapping::new(),
+                multi_use_claims: Mapping::new(),
+                vesting_terms: Mapping::new(),
+                vestings: Mapping::new(),
+                rekeys: Mapping::new(),
+            };
+
+            contract.version.set(&STORAGE_VERSION);
+            contract
         }
 
-        /// Set new `coupon` with declared amount.
+        /// Set new `coupon` with declared amount paid in native currency, without expiry,
+        /// its signatures are verified with `sr25519` key, see `add_coupon_with_options`.
         /// - Coupon is accepted only if the contract has enough balance.
-        /// - Only the `owner` can set a new `coupon`.
+        /// - Only the `Issuer` can set a new `coupon`.
         /// Returns: if added - return `amount`, otherwise return none
         #[ink(message)]
         pub fn add_coupon(&mut self, coupon: CouponId, amount: Balance) -> Result<Balance, Error> {
-            (Self::env().caller() == self.owner)
-                .then(|| true)
-                .ok_or(Error::AccessOwner)
+            self.add_coupon_with_options(coupon, amount, None, None, KeyScheme::Sr25519)
+        }
+
+        /// Set new `coupon` with declared amount and optional `expires`.
+        /// Coupon is paid in PSP22 `token` if set, otherwise in native currency,
+        /// its signatures are verified with the key `scheme`.
+        /// - Coupon is accepted only if the contract has enough balance.
+        /// - Only the `Issuer` can set a new `coupon`.
+        /// Returns: if added - return `amount`, otherwise return none
+        #[ink(message)]
+        pub fn add_coupon_with_options(
+            &mut self,
+            coupon: CouponId,
+            amount: Balance,
+            expires: Option<Expiry>,
+            token: Option<TokenId>,
+            scheme: KeyScheme,
+        ) -> Result<Balance, Error> {
+            self.ensure_role(Role::Issuer)
+                .and_then(|_| self.ensure_not_paused(self.issuance_paused.get()))
+                .and_then(|_| self.check_expiry(expires))
                 .and_then(|_| {
-                    (self.rest_balance() >= amount)
-                        .then(|| true)
+                    (self.rest_balance_of(token) >= amount)
+                        .then_some(true)
                         .ok_or(Error::ContractBalanceNotEnough)
-                        .and_then(|_| self.insert_coupon(&coupon, amount))
+                        .and_then(|_| self.insert_coupon(&coupon, amount, expires, token, scheme))
+                })
+        }
+
+        /// Set new vesting `coupon`, same as `add_coupon`. Activation of the coupon starts a vesting
+        /// position of the receiver: nothing is vested until `cliff` and the whole amount is vested
+        /// linearly by the end of `duration` (milliseconds from activation), see `claim_vested`.
+        /// The `cliff` can't be longer than the `duration`, zero `duration` vests the whole amount on activation.
+        /// The `Revoker` can cancel the unvested part of `revocable` positions.
+        /// With `campaign` the coupon is issued into the campaign paid in the same `token`,
+        /// same as `add_campaign_coupons`, campaign totals are updated on claims and cancellation.
+        /// Returns: if added - return `amount`, otherwise return none
+        #[ink(message)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn add_vesting_coupon(
+            &mut self,
+            coupon: CouponId,
+            amount: Balance,
+            expires: Option<Expiry>,
+            token: Option<TokenId>,
+            cliff: Timestamp,
+            duration: Timestamp,
+            revocable: bool,
+            scheme: KeyScheme,
+            campaign: Option<CampaignId>,
+        ) -> Result<Balance, Error> {
+            (cliff <= duration)
+                .then_some(())
+                .ok_or(Error::InvalidVestingTerms)
+                .and_then(|_| match campaign {
+                    Some(campaign_id) => self
+                        .campaigns
+                        .get(campaign_id)
+                        .ok_or(Error::CampaignNotFound)
+                        .and_then(|campaign| {
+                            (campaign.token == token)
+                                .then_some(())
+                                .ok_or(Error::CampaignTokenMismatch)
+                        })
+                        .and_then(|_| {
+                            self.add_campaign_coupons(
+                                campaign_id,
+                                Vec::from([(coupon, amount)]),
+                                expires,
+                                scheme,
+                            )
+                        })
+                        .and_then(|mut result| match result.declined.pop() {
+                            Some((_, error)) => Err(error),
+                            None => Ok(amount),
+                        }),
+                    None => self.add_coupon_with_options(coupon, amount, expires, token, scheme),
+                })
+                .inspect(|_| {
+                    self.vesting_terms.insert(
+                        coupon,
+                        &VestingTerms {
+                            cliff,
+                            duration,
+                            revocable,
+                        },
+                    );
+                })
+        }
+
+        /// Set new multi-use `coupon` paying `amount` once per receiver up to `max_redemptions` times,
+        /// the total amount is reserved on registration, same as `add_coupon`.
+        /// Unused remainder is released when the coupon is revoked or reclaimed.
+        /// Returns: if added - return the total amount, otherwise return none
+        #[ink(message)]
+        pub fn add_multi_use_coupon(
+            &mut self,
+            coupon: CouponId,
+            amount: Balance,
+            max_redemptions: u32,
+            expires: Option<Expiry>,
+            token: Option<TokenId>,
+            scheme: KeyScheme,
+        ) -> Result<Balance, Error> {
+            (amount > 0 && max_redemptions > 0)
+                .then_some(())
+                .ok_or(Error::InvalidMultiUseTerms)
+                .and_then(|_| {
+                    amount
+                        .checked_mul(max_redemptions.into())
+                        .ok_or(Error::ArithmeticOverflow)
+                })
+                .and_then(|total| self.add_coupon_with_options(coupon, total, expires, token, scheme))
+                .inspect(|_| {
+                    self.multi_use.insert(
+                        coupon,
+                        &MultiUse {
+                            amount,
+                            max_redemptions,
+                            redemptions: 0,
+                        },
+                    );
+                })
+        }
+
+        /// Redemptions of the multi-use `coupon`, none for single-use coupons
+        #[ink(message)]
+        pub fn coupon_uses(&self, coupon: CouponId) -> Option<MultiUse> {
+            self.multi_use.get(coupon)
+        }
+
+        /// Set new `coupon` bound to the allowlist of `receivers` (up to 10), same as `add_coupon`.
+        /// The coupon pays only to these receivers, it isn't a bearer coupon.
+        /// Returns: if added - return `amount`, otherwise return none
+        #[ink(message)]
+        pub fn add_bound_coupon(
+            &mut self,
+            coupon: CouponId,
+            amount: Balance,
+            expires: Option<Expiry>,
+            token: Option<TokenId>,
+            receivers: Vec<ReceiverAddress>,
+            scheme: KeyScheme,
+        ) -> Result<Balance, Error> {
+            (!receivers.is_empty() && receivers.len() <= MAX_BOUND_RECEIVERS)
+                .then_some(())
+                .ok_or(Error::InvalidReceiversCount)
+                .and_then(|_| self.add_coupon_with_options(coupon, amount, expires, token, scheme))
+                .inspect(|_| {
+                    self.bound_receivers.insert(coupon, &receivers);
                 })
         }
 
-        /// Set array `max 5 items` of `coupon` with declared per key.
+        /// Set list of `coupons` (up to `max_batch_size`) with declared per key amount and optional `expires`.
+        /// Coupons are paid in PSP22 `token` if set, otherwise in native currency.
         /// - Accept only if the contract has enough balance.
-        /// - Only the `owner` can set a new `coupon`.
+        /// - Only the `Issuer` can set a new `coupon`.
         /// Returns: returns struct with accepted (added & active) and declined coupons (if balance is not enough)
         #[ink(message)]
-        pub fn add_coupons(&mut self, coupons: OptCoupons, amount: Balance) -> Result<CouponsResult, Error> {
-            (Self::env().caller() == self.owner)
-                .then(|| true)
-                .ok_or(Error::AccessOwner)
+        pub fn add_coupons(
+            &mut self,
+            coupons: Coupons,
+            amount: Balance,
+            expires: Option<Expiry>,
+            token: Option<TokenId>,
+            scheme: KeyScheme,
+        ) -> Result<CouponsResult, Error> {
+            self.ensure_role(Role::Issuer)
+                .and_then(|_| self.ensure_not_paused(self.issuance_paused.get()))
+                .and_then(|_| self.check_batch_size(coupons.len()))
+                .and_then(|_| self.check_expiry(expires))
                 .and_then(|_| {
-                    (self.rest_balance() >= amount)
-                        .then(|| true)
+                    (self.rest_balance_of(token) >= amount)
+                        .then_some(())
                         .ok_or(Error::ContractBalanceNotEnough)
                 })
-                .and_then(|_| {
-                    Ok(coupons.into_iter().fold(
-                        (
-                            CouponsResult::default(),
-                            self.rest_balance(),
-                            0 as usize,
-                            0 as usize,
-                        ),
-                        |(mut result, mut rest_balance, mut la, mut ld), opt| {
-                            if let (Some(coupon), Some(true)) = (opt, Some(rest_balance >= amount)) {
-                                if self.insert_coupon(&coupon, amount.clone()).is_ok() {
-                                    result.accepted[la] = Some(coupon);
+                .map(|_| {
+                    coupons.into_iter().fold(
+                        (CouponsResult::default(), self.rest_balance_of(token)),
+                        |(mut result, mut rest_balance), coupon| {
+                            if rest_balance >= amount
+                                && self
+                                    .insert_coupon(&coupon, amount, expires, token, scheme)
+                                    .is_ok()
+                            {
+                                result.accepted.push(coupon);
+                                rest_balance -= amount;
+                            } else {
+                                result.declined.push(coupon);
+                            }
+
+                            (result, rest_balance)
+                        },
+                    )
+                })
+                .map(|(result, _)| result)
+        }
+
+        /// Set list of `coupons` (up to `max_batch_size`) with individual amounts and optional `expires`.
+        /// Coupons are paid in PSP22 `token` if set, otherwise in native currency.
+        /// - Every coupon is reserved independently while the contract has enough balance.
+        /// - Only the `Issuer` can set a new `coupon`.
+        /// Returns: struct with accepted coupons and declined coupons with the reason
+        /// (`CouponAlreadyExists` or `ContractBalanceNotEnough`)
+        #[ink(message)]
+        pub fn add_coupons_with_amounts(
+            &mut self,
+            coupons: Vec<(CouponId, Balance)>,
+            expires: Option<Expiry>,
+            token: Option<TokenId>,
+            scheme: KeyScheme,
+        ) -> Result<CouponsAmountsResult, Error> {
+            self.ensure_role(Role::Issuer)
+                .and_then(|_| self.ensure_not_paused(self.issuance_paused.get()))
+                .and_then(|_| self.check_batch_size(coupons.len()))
+                .and_then(|_| self.check_expiry(expires))
+                .map(|_| {
+                    coupons.into_iter().fold(
+                        (CouponsAmountsResult::default(), self.rest_balance_of(token)),
+                        |(mut result, mut rest_balance), (coupon, amount)| {
+                            let inserted = (rest_balance >= amount)
+                                .then_some(())
+                                .ok_or(Error::ContractBalanceNotEnough)
+                                .and_then(|_| self.insert_coupon(&coupon, amount, expires, token, scheme));
+
+                            match inserted {
+                                Ok(_) => {
+                                    result.accepted.push(coupon);
                                     rest_balance -= amount;
-                                    la += 1;
-                                } else {
-                                    result.declined[ld] = Some(coupon);
-                                    ld += 1;
                                 }
-                            } else {
-                                result.declined[ld] = opt;
-                                ld += 1;
+                                Err(error) => result.declined.push((coupon, error)),
                             }
 
-                            return (result, rest_balance, la, ld);
+                            (result, rest_balance)
                         },
-                    ))
+                    )
                 })
-                .and_then(|(result, _, _, _)| Ok(result))
+                .map(|(result, _)| result)
         }
 
         /// Activate `coupon` with transfer of appropriate liquidity to a receiver's address.
-        /// Verified by `sr25519` `signature` with `receiver address`
-        /// with `contract id` context
+        /// Verified by legacy `signature` of the coupon key of `receiver address` in the coupon key scheme
+        /// while legacy signatures are enabled by the `Admin`, see `activate_coupon_with_deadline`.
         ///
         /// Returns: boolean success if all valid
         #[ink(message)]
@@ -163,311 +1305,4393 @@ mod ocex {
             coupon: CouponId,
             sign: [u8; 64],
         ) -> Result<bool, Error> {
-            self.coupons
-                .get(&coupon)
-                .ok_or(Error::InvalidParseCoupon)
-                .and_then(|coupon_amount| {
-                    // check that coupons aren't burned
-                    self.burned
-                        .get(&coupon)
-                        .is_none()
-                        .then(|| coupon_amount)
-                        .ok_or(Error::CouponAlreadyBurned)
-                })
-                .and_then(|coupon_amount| {
-                    // parsing & cast coupon key
-                    let public_key =
-                        PublicKey::from_bytes(coupon.as_ref()).or(Err(Error::InvalidParseCoupon))?;
+            self.activate(transfer_to, coupon, None, sign)
+        }
 
-                    Ok((coupon_amount, public_key))
-                })
-                .and_then(|(coupon_amount, public_key)| {
-                    // parsing & cast signature
-                    let signature =
-                        Signature::from_bytes(&sign).or(Err(Error::InvalidParseCouponSignature))?;
+        /// Activate `coupon` with transfer of appropriate liquidity to a receiver's address.
+        /// Verified by `signature` of the coupon key of SCALE encoded `ActivationPayload`
+        /// valid until the `deadline` block, in the coupon key scheme.
+        ///
+        /// Returns: boolean success if all valid
+        #[ink(message)]
+        pub fn activate_coupon_with_deadline(
+            &mut self,
+            transfer_to: ReceiverAddress,
+            coupon: CouponId,
+            deadline: BlockNumber,
+            sign: [u8; 64],
+        ) -> Result<bool, Error> {
+            self.activate(transfer_to, coupon, Some(deadline), sign)
+        }
 
-                    Ok((coupon_amount, public_key, signature))
-                })
-                .and_then(|(coupon_amount, public_key, signature)| {
-                    let context = signing_context(Self::env().account_id().as_ref());
+        /// Redeem part of the `coupon` balance with transfer to a receiver's address.
+        /// Verified by `signature` of the coupon key of SCALE encoded `RedemptionPayload` valid until
+        /// the `deadline` block, in the coupon key scheme, where `nonce` is the current coupon nonce
+        /// (see `coupon_nonce`). Pays out up to the rest of the coupon balance, the coupon is burned
+        /// when it is spent.
+        ///
+        /// Returns: paid out amount
+        #[ink(message)]
+        pub fn redeem_coupon(
+            &mut self,
+            transfer_to: ReceiverAddress,
+            coupon: CouponId,
+            amount: Balance,
+            deadline: BlockNumber,
+            sign: [u8; 64],
+        ) -> Result<Balance, Error> {
+            let nonce = self.coupon_nonce(coupon);
+            let token = self.tokens.get(coupon);
 
-                    // verify signature payload with context by coupon key
-                    public_key
-                        .verify(context.bytes(transfer_to.as_ref()), &signature)
-                        .or(Err(Error::VerifySignatureFailed))
-                        .and_then(|_| Ok(coupon_amount))
+            self.ensure_not_paused(self.redemption_paused.get())
+                .and_then(|_| (amount > 0).then_some(()).ok_or(Error::ZeroRedeemAmount))
+                .and_then(|_| {
+                    (!self.multi_use.contains(coupon))
+                        .then_some(())
+                        .ok_or(Error::MultiUseCoupon)
+                })
+                .and_then(|_| {
+                    (!self.vesting_terms.contains(coupon))
+                        .then_some(())
+                        .ok_or(Error::VestingCoupon)
                 })
+                .and_then(|_| self.active_coupon(&coupon, transfer_to))
                 .and_then(|coupon_amount| {
-                    // check that contract balance is enough for transfer
-                    (coupon_amount <= self.env().balance())
-                        .then(|| coupon_amount)
-                        .ok_or(Error::ContractBalanceNotEnough)
+                    let payload = RedemptionPayload {
+                        domain: self.payload_domain(PayloadAction::Redemption),
+                        coupon,
+                        receiver: transfer_to,
+                        amount,
+                        nonce,
+                        deadline,
+                    };
+
+                    // verify signature of the redemption payload by coupon key
+                    self.check_deadline(deadline)
+                        .and_then(|_| self.verify_signature(&coupon, &payload.encode(), &sign))
+                        .map(|_| coupon_amount)
                 })
                 .and_then(|coupon_amount| {
-                    // transfer funds to verified receiver
-                    self.env()
-                        .transfer(transfer_to, coupon_amount)
-                        .or_else(|_| Err(Error::TransferFailed))
-                        .and_then(|_| Ok(coupon_amount))
+                    self.payout(token, transfer_to, amount.min(coupon_amount))
+                        .map(|payout| (payout, coupon_amount - payout))
                 })
-                .and_then(|_| self.burn_coupon(&coupon))
-        }
+                .and_then(|(payout, rest_amount)| {
+                    self.spend_coupon(&coupon, transfer_to, payout, 0)?;
+                    self.nonces.insert(coupon, &(nonce + 1));
 
-        /// Method for transferring spare balance (not reserved for coupons)
-        /// to owner's wallet. (for example, if you've transferred more funds
-        /// to the smart-contract that was necessary)
-        #[ink(message)]
-        pub fn payback_not_reserved_funds(&mut self) -> Result<bool, Error> {
-            (Self::env().caller() == self.owner)
-                .then(|| true)
-                .ok_or(Error::AccessOwner)
-                .and_then(|_| Ok(self.rest_balance()))
-                .and_then(|rest_balance| {
-                    // transfer funds to verified receiver
-                    self.env()
-                        .transfer(self.owner, rest_balance)
-                        .or_else(|_| Err(Error::TransferFailed))
+                    self.env().emit_event(CouponRedeemed {
+                        coupon,
+                        receiver: transfer_to,
+                        amount: payout,
+                        rest_amount,
+                    });
+
+                    Ok(payout)
                 })
-                .and_then(|_| Ok(true))
         }
 
-        /// Method for disabling and burning registered (but not redeemed) coupons.
-        /// The contract unlocks reserved funds. Burned coupons can't be reactivated later.
+        /// Activate `coupon` by a relayer paying the transaction fees instead of the receiver.
+        /// Verified by `signature` of the coupon key of SCALE encoded `RelayPayload` valid until the
+        /// `deadline` block, in the coupon key scheme. The signed `fee` within `relayer_fee_limits` is paid
+        /// to the caller from the coupon amount, the rest is transferred to the receiver.
+        /// If the `relayer` is signed, only this account can relay the activation.
+        ///
+        /// Returns: amount transferred to the receiver
         #[ink(message)]
-        pub fn burn_coupons(&mut self, coupons: OptCoupons) -> Result<CouponsResult, Error> {
-            (Self::env().caller() == self.owner)
-                .then(|| true)
-                .ok_or(Error::AccessOwner)
-                .and_then(|_| {
-                    Ok(coupons.into_iter().fold(
-                        (CouponsResult::default(), 0 as usize, 0 as usize),
-                        |(mut result, mut la, mut ld), opt| {
-                            if let Some(coupon) = opt {
-                                if self.burn_coupon(&coupon).is_ok() {
-                                    result.accepted[la] = Some(coupon);
-                                    la += 1;
-                                } else {
-                                    result.declined[ld] = Some(coupon);
-                                    ld += 1;
-                                }
-                            }
+        pub fn relay_activate_coupon(
+            &mut self,
+            transfer_to: ReceiverAddress,
+            coupon: CouponId,
+            fee: Balance,
+            relayer: Option<AccountId>,
+            deadline: BlockNumber,
+            sign: [u8; 64],
+        ) -> Result<Balance, Error> {
+            let caller = Self::env().caller();
+            let token = self.tokens.get(coupon);
 
-                            return (result, la, ld);
-                        },
-                    ))
+            self.ensure_not_paused(self.redemption_paused.get())
+                .and_then(|_| {
+                    relayer
+                        .is_none_or(|relayer| relayer == caller)
+                        .then_some(())
+                        .ok_or(Error::InvalidRelayer)
                 })
-                .and_then(|(result, _, _)| Ok(result))
-        }
+                .and_then(|_| {
+                    (!self.vesting_terms.contains(coupon))
+                        .then_some(())
+                        .ok_or(Error::VestingCoupon)
+                })
+                .and_then(|_| self.active_coupon(&coupon, transfer_to))
+                .and_then(|coupon_amount| self.activation_amount(&coupon, transfer_to, coupon_amount))
+                .and_then(|coupon_amount| {
+                    let payload = RelayPayload {
+                        domain: self.payload_domain(PayloadAction::Relay),
+                        coupon,
+                        receiver: transfer_to,
+                        fee,
+                        relayer,
+                        deadline,
+                    };
+
+                    // verify signature of the relay payload by coupon key
+                    self.check_deadline(deadline)
+                        .and_then(|_| self.verify_signature(&coupon, &payload.encode(), &sign))
+                        .map(|_| coupon_amount)
+                })
+                .and_then(|coupon_amount| self.check_relayer_fee(fee, coupon_amount).map(|_| coupon_amount))
+                .and_then(|coupon_amount| {
+                    let amount = coupon_amount
+                        .checked_sub(fee)
+                        .ok_or(Error::RelayerFeeTooHigh)
+                        .and_then(|amount| self.payout(token, transfer_to, amount))?;
+
+                    if fee > 0 {
+                        self.payout(token, caller, fee)?;
+                    }
+
+                    self.spend_coupon(&coupon, transfer_to, coupon_amount, fee)?;
+                    self.env().emit_event(CouponActivated {
+                        coupon,
+                        receiver: transfer_to,
+                        amount,
+                    });
+                    if fee > 0 {
+                        self.env().emit_event(RelayerFeePaid {
+                            coupon,
+                            relayer: caller,
+                            fee,
+                        });
+                    }
+
+                    Ok(amount)
+                })
+        }
+
+        /// Move the `coupon` to the `new_coupon` key,
+        /// e.g. if the coupon secret is leaked or to gift the coupon.
+        /// Verified by `signature` of the coupon key of SCALE encoded `RekeyPayload`
+        /// (domain header, coupon, new coupon). Balance, metadata and reservation
+        /// are moved to the new key with the same key scheme, the old key is nullified.
+        /// Multi-use coupons can't be rekeyed.
+        /// Returns: moved coupon balance
+        #[ink(message)]
+        pub fn rekey_coupon(
+            &mut self,
+            coupon: CouponId,
+            new_coupon: CouponId,
+            sign: [u8; 64],
+        ) -> Result<Balance, Error> {
+            self.ensure_not_paused(self.redemption_paused.get())
+                .and_then(|_| {
+                    (!self.multi_use.contains(coupon))
+                        .then_some(())
+                        .ok_or(Error::MultiUseCoupon)
+                })
+                .and_then(|_| self.check_state(&coupon))
+                .and_then(|_| self.coupons.get(coupon).ok_or(Error::CouponNotFound))
+                .and_then(|amount| {
+                    (!self.is_expired(&coupon))
+                        .then_some(amount)
+                        .ok_or(Error::CouponExpired)
+                })
+                .and_then(|amount| {
+                    // pruned coupons keep only the terminal state, so they can't be reused
+                    (!self.coupons.contains(new_coupon) && !self.burned.contains(new_coupon))
+                        .then_some(amount)
+                        .ok_or(Error::CouponAlreadyExists)
+                })
+                .and_then(|amount| {
+                    // verify signature of the new key by the old coupon key
+                    let payload = RekeyPayload {
+                        domain: self.payload_domain(PayloadAction::Rekey),
+                        coupon,
+                        new_coupon,
+                    };
+
+                    self.verify_signature(&coupon, &payload.encode(), &sign)
+                        .map(|_| amount)
+                })
+                .inspect(|&amount| {
+                    self.move_coupon(&coupon, &new_coupon);
+                    self.env().emit_event(CouponRekeyed {
+                        coupon,
+                        new_coupon,
+                        amount,
+                    });
+                })
+        }
+
+        /// Cancel the `coupon` by the holder declining it, e.g. for compliance reasons.
+        /// Verified by `signature` of the coupon key of SCALE encoded `CancellationPayload`
+        /// (domain header, coupon). The coupon reservation is released
+        /// to spare funds and the coupon is marked `Cancelled`, unlike `Revoked` by the owner.
+        /// Multi-use coupons can't be cancelled by the holder.
+        /// Returns: released coupon balance
+        #[ink(message)]
+        pub fn cancel_coupon(&mut self, coupon: CouponId, sign: [u8; 64]) -> Result<Balance, Error> {
+            self.ensure_not_paused(self.redemption_paused.get())
+                .and_then(|_| {
+                    (!self.multi_use.contains(coupon))
+                        .then_some(())
+                        .ok_or(Error::MultiUseCoupon)
+                })
+                .and_then(|_| self.check_state(&coupon))
+                .and_then(|_| self.coupons.get(coupon).ok_or(Error::CouponNotFound))
+                // expired coupons are reclaimed by the `Revoker`
+                .and_then(|amount| {
+                    (!self.is_expired(&coupon))
+                        .then_some(amount)
+                        .ok_or(Error::CouponExpired)
+                })
+                .and_then(|amount| {
+                    // verify signature of the cancellation by coupon key
+                    let payload = CancellationPayload {
+                        domain: self.payload_domain(PayloadAction::Cancellation),
+                        coupon,
+                    };
+
+                    self.verify_signature(&coupon, &payload.encode(), &sign)
+                        .map(|_| amount)
+                })
+                .and_then(|amount| {
+                    self.burn_coupon(&coupon, CouponState::Cancelled)?;
+                    self.env().emit_event(CouponCancelled { coupon, amount });
+
+                    Ok(amount)
+                })
+        }
+
+        /// Transfer the vested and not yet claimed amount of the vesting `coupon`
+        /// to the receiver of the position, allowed only for the receiver.
+        /// Returns: claimed amount
+        #[ink(message)]
+        pub fn claim_vested(&mut self, coupon: CouponId) -> Result<Balance, Error> {
+            self.ensure_not_paused(self.redemption_paused.get())
+                .and_then(|_| self.vestings.get(coupon).ok_or(Error::VestingNotFound))
+                .and_then(|vesting| {
+                    (vesting.receiver == Self::env().caller())
+                        .then_some(vesting)
+                        .ok_or(Error::NotVestingReceiver)
+                })
+                .and_then(|vesting| {
+                    let amount =
+                        Self::vested_amount(&vesting, Self::env().block_timestamp())? - vesting.claimed;
+
+                    (amount > 0)
+                        .then_some((vesting, amount))
+                        .ok_or(Error::NothingToClaim)
+                })
+                .and_then(|(mut vesting, amount)| {
+                    self.payout(vesting.token, vesting.receiver, amount)?;
+
+                    self.release_campaign(vesting.campaign, amount, CampaignRelease::Redeemed)?;
+
+                    self.release(vesting.token, amount)?;
+
+                    vesting.claimed += amount;
+                    self.vestings.insert(coupon, &vesting);
+
+                    self.env().emit_event(VestedClaimed {
+                        coupon,
+                        receiver: vesting.receiver,
+                        amount,
+                    });
+
+                    Ok(amount)
+                })
+        }
+
+        /// Cancel the unvested part of the revocable vesting `coupon` and release its reservation,
+        /// the vested part can still be claimed by the receiver. Allowed only for the `Revoker`.
+        /// Returns: released amount
+        #[ink(message)]
+        pub fn cancel_vesting(&mut self, coupon: CouponId) -> Result<Balance, Error> {
+            self.ensure_role(Role::Revoker)
+                .and_then(|_| self.vestings.get(coupon).ok_or(Error::VestingNotFound))
+                .and_then(|vesting| {
+                    (vesting.terms.revocable && vesting.cancelled.is_none())
+                        .then_some(vesting)
+                        .ok_or(Error::VestingNotRevocable)
+                })
+                .and_then(|mut vesting| {
+                    let now = Self::env().block_timestamp();
+                    let amount = vesting.total - Self::vested_amount(&vesting, now)?;
+
+                    self.release_campaign(vesting.campaign, amount, CampaignRelease::Burned)?;
+
+                    self.release(vesting.token, amount)?;
+
+                    vesting.cancelled = Some(now);
+                    self.vestings.insert(coupon, &vesting);
+
+                    self.env().emit_event(VestingCancelled { coupon, amount });
+
+                    Ok(amount)
+                })
+        }
+
+        /// Vesting position of the activated vesting `coupon`
+        #[ink(message)]
+        pub fn vesting(&self, coupon: CouponId) -> Option<Vesting> {
+            self.vestings.get(coupon)
+        }
+
+        /// Method for transferring spare balance (not reserved for coupons)
+        /// to owner's wallet. (for example, if you've transferred more funds
+        /// to the smart-contract that was necessary)
+        /// Allowed only for the `Treasurer`.
+        #[ink(message)]
+        pub fn payback_not_reserved_funds(&mut self) -> Result<bool, Error> {
+            self.payback_not_reserved(None)
+        }
+
+        /// Method for transferring spare PSP22 `token` balance (not reserved for coupons)
+        /// to owner's wallet. Allowed only for the `Treasurer`.
+        #[ink(message)]
+        pub fn payback_not_reserved_tokens(&mut self, token: TokenId) -> Result<bool, Error> {
+            self.payback_not_reserved(Some(token))
+        }
+
+        /// Method for disabling and burning registered (but not redeemed) coupons.
+        /// The contract unlocks reserved funds. Burned coupons can't be reactivated later.
+        /// Allowed only for the `Revoker`.
+        #[ink(message)]
+        pub fn burn_coupons(&mut self, coupons: Coupons) -> Result<CouponsResult, Error> {
+            self.ensure_role(Role::Revoker)
+                .and_then(|_| self.check_batch_size(coupons.len()))
+                .map(|_| {
+                    coupons
+                        .into_iter()
+                        .fold(CouponsResult::default(), |mut result, coupon| {
+                            let amount = self.coupons.get(coupon).unwrap_or_default();
+
+                            if self.burn_coupon(&coupon, CouponState::Revoked).is_ok() {
+                                self.env().emit_event(CouponBurned { coupon, amount });
+                                result.accepted.push(coupon);
+                            } else {
+                                result.declined.push(coupon);
+                            }
+
+                            result
+                        })
+                })
+        }
+
+        /// Method for burning expired (but not redeemed) coupons.
+        /// The contract unlocks reserved funds, coupons without expiry
+        /// or with expiry in the future are declined. Allowed only for the `Revoker`.
+        #[ink(message)]
+        pub fn reclaim_expired_coupons(&mut self, coupons: Coupons) -> Result<CouponsResult, Error> {
+            self.ensure_role(Role::Revoker)
+                .and_then(|_| self.check_batch_size(coupons.len()))
+                .map(|_| {
+                    coupons
+                        .into_iter()
+                        .fold(CouponsResult::default(), |mut result, coupon| {
+                            let amount = self.coupons.get(coupon).unwrap_or_default();
+
+                            if self.is_expired(&coupon)
+                                && self.burn_coupon(&coupon, CouponState::Expired).is_ok()
+                            {
+                                self.env().emit_event(CouponExpired { coupon, amount });
+                                result.accepted.push(coupon);
+                            } else {
+                                result.declined.push(coupon);
+                            }
+
+                            result
+                        })
+                })
+        }
+
+        /// Verification that the coupon is registered and it's value.
+        /// Expired coupons and coupons out of the campaign period are reported as not active.
+        /// See `coupon_info` for the detailed status and expiry.
+        #[ink(message)]
+        pub fn check_coupon(&self, coupon: CouponId) -> (bool, Balance) {
+            let info = self.coupon_info(coupon);
+            let amount = match info.status {
+                // redeemed coupon keeps reporting the amount it was redeemed with
+                CouponStatus::Redeemed => self
+                    .receipts
+                    .get(coupon)
+                    .map_or(0, |receipt| receipt.total)
+                    .saturating_add(info.amount),
+                _ => info.amount,
+            };
+
+            (info.status == CouponStatus::Active, amount)
+        }
+
+        /// Detailed coupon info with status, rest amount, issuance and last redemption
+        #[ink(message)]
+        pub fn coupon_info(&self, coupon: CouponId) -> CouponInfo {
+            let amount = self.coupons.get(coupon);
+            let token = self.tokens.get(coupon);
+            let issuance = self.issuances.get(coupon);
+            let receipt = self.receipts.get(coupon);
+
+            let status = match (amount, self.burned.get(coupon)) {
+                (_, Some(state)) => state.into(),
+                (None, None) => CouponStatus::NotRegistered,
+                (Some(_), None) if self.is_expired(&coupon) => CouponStatus::Expired,
+                (Some(_), None) if self.check_campaign(&coupon).is_err() => CouponStatus::Inactive,
+                (Some(amount), None) if self.asset_balance(token) < amount => CouponStatus::Underfunded,
+                (Some(_), None) => CouponStatus::Active,
+            };
+
+            CouponInfo {
+                status,
+                amount: amount.unwrap_or_default(),
+                token,
+                expires: self.expires.get(coupon),
+                campaign: self.coupon_campaigns.get(coupon),
+                issuer: issuance.as_ref().map(|issuance| issuance.issuer),
+                issued_at: issuance.map(|issuance| issuance.timestamp),
+                receiver: receipt.as_ref().map(|receipt| receipt.receiver),
+                redeemed_at: receipt.map(|receipt| receipt.timestamp),
+                receivers: self.bound_receivers.get(coupon).unwrap_or_default(),
+            }
+        }
+
+        /// Receipt of the redeemed `coupon`: receiver, paid amount, block number & timestamp
+        /// of the last redemption and the total redeemed amount
+        #[ink(message)]
+        pub fn coupon_receipt(&self, coupon: CouponId) -> Option<Receipt> {
+            self.receipts.get(coupon)
+        }
+
+        /// Count of coupons redemptions (including airdrop claims) paid to the `receiver`
+        #[ink(message)]
+        pub fn receiver_redemptions(&self, receiver: ReceiverAddress) -> u32 {
+            self.receiver_redemptions.get(receiver).unwrap_or_default()
+        }
+
+        /// Key scheme of the `coupon` signatures
+        #[ink(message)]
+        pub fn coupon_scheme(&self, coupon: CouponId) -> KeyScheme {
+            self.schemes.get(coupon).unwrap_or_default()
+        }
+
+        /// PSP22 token of the `coupon`, none if the coupon is paid in native currency
+        #[ink(message)]
+        pub fn coupon_token(&self, coupon: CouponId) -> Option<TokenId> {
+            self.tokens.get(coupon)
+        }
+
+        /// Current nonce of the `coupon` to sign the next partial redemption
+        #[ink(message)]
+        pub fn coupon_nonce(&self, coupon: CouponId) -> u32 {
+            self.nonces.get(coupon).unwrap_or_default()
+        }
+
+        /// Page of registered coupons starting from the `cursor` position, up to `limit`
+        /// (at most `max_batch_size`) coupons. Without `status_filter` coupons are listed
+        /// in the insertion order, otherwise from the status list: `Active` (not redeemed, revoked
+        /// or reclaimed yet, including expired coupons until they are reclaimed), `Redeemed`, `Revoked`,
+        /// `Reclaimed`, `Rekeyed` or `Cancelled` (declined by the holder). Other statuses depend
+        /// on the time or the contract balance and aren't indexed, they are rejected as filters.
+        /// Status lists are reordered when coupons leave them.
+        /// Returns: coupons and the cursor of the next page, none if it's the last page
+        #[ink(message)]
+        pub fn list_coupons(
+            &self,
+            cursor: u32,
+            limit: u32,
+            status_filter: Option<CouponStatus>,
+        ) -> Result<(Coupons, Option<u32>), Error> {
+            let indexed = !matches!(
+                status_filter,
+                Some(
+                    CouponStatus::NotRegistered
+                        | CouponStatus::Underfunded
+                        | CouponStatus::Inactive
+                        | CouponStatus::Expired
+                )
+            );
+
+            indexed.then_some(()).ok_or(Error::StatusNotIndexed).map(|_| {
+                let count = match status_filter {
+                    Some(status) => self.status_counts.get(status).unwrap_or_default(),
+                    None => self.coupons_total.get().unwrap_or_default(),
+                };
+                let to = count.min(cursor.saturating_add(limit.min(self.max_batch_size())));
+
+                let coupons = (cursor..to)
+                    .filter_map(|index| match status_filter {
+                        Some(status) => self.status_coupons.get((status, index)),
+                        None => self.coupons_index.get(index),
+                    })
+                    .collect();
+
+                (coupons, (to < count).then_some(to))
+            })
+        }
+
+        /// Counts of all registered coupons and coupons by the indexed status
+        #[ink(message)]
+        pub fn coupon_counts(&self) -> CouponCounts {
+            let count = |status| self.status_counts.get(status).unwrap_or_default();
+
+            CouponCounts {
+                total: self.coupons_total.get().unwrap_or_default(),
+                active: count(CouponStatus::Active),
+                redeemed: count(CouponStatus::Redeemed),
+                revoked: count(CouponStatus::Revoked),
+                reclaimed: count(CouponStatus::Reclaimed),
+                rekeyed: count(CouponStatus::Rekeyed),
+                cancelled: count(CouponStatus::Cancelled),
+                pruned: self.pruned_total.get().unwrap_or_default(),
+            }
+        }
+
+        /// Get info on spare funds of the contract (not reserved for coupons)
+        /// available for withdrawal
+        /// Allow request only from the `Treasurer`, otherwise return zero
+        #[ink(message)]
+        pub fn available_balance(&mut self) -> Balance {
+            if self.has_role(Role::Treasurer, Self::env().caller()) {
+                self.rest_balance()
+            } else {
+                0
+            }
+        }
+
+        /// Get info on spare PSP22 `token` funds of the contract (not reserved for coupons)
+        /// available for withdrawal
+        /// Allow request only from the `Treasurer`, otherwise return zero
+        #[ink(message)]
+        pub fn available_token_balance(&mut self, token: TokenId) -> Balance {
+            if self.has_role(Role::Treasurer, Self::env().caller()) {
+                self.rest_balance_of(Some(token))
+            } else {
+                0
+            }
+        }
+
+        /// Create a campaign with `label`, `budget` cap for issued coupons paid in
+        /// PSP22 `token` (or native currency) and optional redemption period.
+        /// Zero `budget` and a period with `starts` not before `ends` are rejected.
+        /// Allowed only for the `Issuer`.
+        /// Returns: id of the new campaign
+        #[ink(message)]
+        pub fn create_campaign(
+            &mut self,
+            label: String,
+            budget: Balance,
+            token: Option<TokenId>,
+            starts: Option<Timestamp>,
+            ends: Option<Timestamp>,
+        ) -> Result<CampaignId, Error> {
+            self.ensure_role(Role::Issuer)
+                .and_then(|_| self.ensure_not_paused(self.issuance_paused.get()))
+                .and_then(|_| {
+                    (budget > 0 && starts.zip(ends).filter(|(starts, ends)| starts >= ends).is_none())
+                        .then_some(())
+                        .ok_or(Error::InvalidCampaignTerms)
+                })
+                .map(|_| {
+                    let campaign = self.campaigns_count.get().unwrap_or_default();
+
+                    self.campaigns.insert(
+                        campaign,
+                        &Campaign {
+                            label: label.clone(),
+                            token,
+                            budget,
+                            starts,
+                            ends,
+                            issued: 0,
+                            redeemed: 0,
+                            burned: 0,
+                            reserved: 0,
+                            coupons: 0,
+                            closed_coupons: 0,
+                            closed: false,
+                        },
+                    );
+                    self.campaigns_count.set(&(campaign + 1));
+
+                    self.env().emit_event(CampaignCreated {
+                        campaign,
+                        label,
+                        budget,
+                    });
+
+                    campaign
+                })
+        }
+
+        /// Set list of `coupons` (up to `max_batch_size`) with individual amounts into the `campaign`.
+        /// - Every coupon is reserved independently within the campaign budget
+        ///   while the contract has enough balance.
+        /// - Only the `Issuer` can set a new `coupon`.
+        /// Returns: struct with accepted coupons and declined coupons with the reason
+        /// (`CouponAlreadyExists`, `CampaignBudgetExceeded` or `ContractBalanceNotEnough`)
+        #[ink(message)]
+        pub fn add_campaign_coupons(
+            &mut self,
+            campaign_id: CampaignId,
+            coupons: Vec<(CouponId, Balance)>,
+            expires: Option<Expiry>,
+            scheme: KeyScheme,
+        ) -> Result<CouponsAmountsResult, Error> {
+            self.ensure_role(Role::Issuer)
+                .and_then(|_| self.ensure_not_paused(self.issuance_paused.get()))
+                .and_then(|_| self.check_batch_size(coupons.len()))
+                .and_then(|_| self.check_expiry(expires))
+                .and_then(|_| self.campaigns.get(campaign_id).ok_or(Error::CampaignNotFound))
+                .and_then(|campaign| {
+                    // issuance is allowed until the campaign is closed or ended
+                    (!campaign.closed
+                        && campaign
+                            .ends
+                            .is_none_or(|ends| Self::env().block_timestamp() < ends))
+                    .then_some(campaign)
+                    .ok_or(Error::CampaignClosed)
+                })
+                .map(|campaign| {
+                    let rest_balance = self.rest_balance_of(campaign.token);
+
+                    coupons.into_iter().fold(
+                        (CouponsAmountsResult::default(), rest_balance, campaign),
+                        |(mut result, mut rest_balance, mut campaign), (coupon, amount)| {
+                            let inserted = campaign
+                                .issued
+                                .checked_add(amount)
+                                .filter(|issued| *issued <= campaign.budget)
+                                .ok_or(Error::CampaignBudgetExceeded)
+                                .and_then(|_| {
+                                    campaign
+                                        .reserved
+                                        .checked_add(amount)
+                                        .ok_or(Error::ArithmeticOverflow)
+                                })
+                                .and_then(|reserved| {
+                                    (rest_balance >= amount)
+                                        .then_some(reserved)
+                                        .ok_or(Error::ContractBalanceNotEnough)
+                                })
+                                .and_then(|reserved| {
+                                    self.insert_coupon(&coupon, amount, expires, campaign.token, scheme)
+                                        .map(|_| reserved)
+                                });
+
+                            match inserted {
+                                Ok(reserved) => {
+                                    self.coupon_campaigns.insert(coupon, &campaign_id);
+                                    self.campaign_coupons
+                                        .insert((campaign_id, campaign.coupons), &coupon);
+
+                                    campaign.coupons += 1;
+                                    campaign.issued += amount;
+                                    campaign.reserved = reserved;
+
+                                    result.accepted.push(coupon);
+                                    rest_balance -= amount;
+                                }
+                                Err(error) => result.declined.push((coupon, error)),
+                            }
+
+                            (result, rest_balance, campaign)
+                        },
+                    )
+                })
+                .map(|(result, _, campaign)| {
+                    self.campaigns.insert(campaign_id, &campaign);
+
+                    result
+                })
+        }
+
+        /// Close the `campaign` and burn its unredeemed coupons, the contract unlocks
+        /// only the campaign reserved funds. Coupons are burned by `max_batch_size` per call,
+        /// repeat the call until it returns zero. Allowed only for the `Revoker`.
+        /// Returns: count of the campaign coupons left to process
+        #[ink(message)]
+        pub fn close_campaign(&mut self, campaign_id: CampaignId) -> Result<u32, Error> {
+            self.ensure_role(Role::Revoker)
+                .and_then(|_| self.campaigns.get(campaign_id).ok_or(Error::CampaignNotFound))
+                .and_then(|mut campaign| {
+                    if !campaign.closed {
+                        campaign.closed = true;
+                        self.campaigns.insert(campaign_id, &campaign);
+                        self.env().emit_event(CampaignClosed {
+                            campaign: campaign_id,
+                        });
+                    }
+
+                    let from = campaign.closed_coupons;
+                    let to = campaign.coupons.min(from.saturating_add(self.max_batch_size()));
+
+                    for index in from..to {
+                        let coupon = self
+                            .campaign_coupons
+                            .get((campaign_id, index))
+                            .map(|coupon| self.rekeyed_coupon(coupon));
+
+                        if let Some(coupon) = coupon {
+                            let amount = self.coupons.get(coupon).unwrap_or_default();
+
+                            if self.burn_coupon(&coupon, CouponState::Revoked).is_ok() {
+                                self.env().emit_event(CouponBurned { coupon, amount });
+                            }
+                        }
+                    }
+
+                    // campaign totals are updated by burned coupons
+                    self.update_campaign(campaign_id, |campaign| {
+                        campaign.closed_coupons = to;
+                        Ok(())
+                    })?;
+
+                    Ok(campaign.coupons - to)
+                })
+        }
+
+        /// Campaign info with issued, redeemed, burned and outstanding (reserved) totals
+        #[ink(message)]
+        pub fn campaign(&self, campaign_id: CampaignId) -> Option<Campaign> {
+            self.campaigns.get(campaign_id)
+        }
+
+        /// Campaign of the `coupon`, none if the coupon isn't issued into a campaign
+        #[ink(message)]
+        pub fn coupon_campaign(&self, coupon: CouponId) -> Option<CampaignId> {
+            self.coupon_campaigns.get(coupon)
+        }
+
+        /// Register a batch of coupons by the Merkle `root` and reserve the total `amount`
+        /// paid in PSP22 `token` (or native currency). Coupons can be claimed until optional `ends`.
+        /// - Accepted only if the contract has enough balance.
+        /// - Allowed only for the `Issuer`.
+        /// Returns: id of the new airdrop
+        #[ink(message)]
+        pub fn add_airdrop(
+            &mut self,
+            root: MerkleHash,
+            amount: Balance,
+            token: Option<TokenId>,
+            ends: Option<Timestamp>,
+        ) -> Result<AirdropId, Error> {
+            self.ensure_role(Role::Issuer)
+                .and_then(|_| self.ensure_not_paused(self.issuance_paused.get()))
+                .and_then(|_| {
+                    (self.rest_balance_of(token) >= amount)
+                        .then_some(())
+                        .ok_or(Error::ContractBalanceNotEnough)
+                })
+                .and_then(|_| self.reserve(token, amount))
+                .map(|_| {
+                    let airdrop = self.airdrops_count.get().unwrap_or_default();
+
+                    // balance is reserved for payout
+                    self.airdrops.insert(
+                        airdrop,
+                        &Airdrop {
+                            root,
+                            token,
+                            ends,
+                            reserved: amount,
+                            claimed: 0,
+                            closed: false,
+                        },
+                    );
+                    self.airdrops_count.set(&(airdrop + 1));
+
+                    self.env().emit_event(AirdropAdded {
+                        airdrop,
+                        root,
+                        amount,
+                    });
+
+                    airdrop
+                })
+        }
+
+        /// Claim airdrop `coupon` with transfer of its `amount` to a receiver's address.
+        /// Verified by Merkle `proof` of the coupon leaf at `index` and `sr25519` `signature`
+        /// of SCALE encoded `AirdropClaimPayload` valid until the `deadline` block, with `contract id`
+        /// context. Without `deadline` the legacy signature of `receiver address` is verified
+        /// while legacy signatures are enabled by the `Admin`. Airdrop leaves don't commit
+        /// the key scheme, so airdrop coupons are always `sr25519` keys.
+        ///
+        /// Returns: boolean success if all valid
+        #[ink(message)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn claim_airdrop(
+            &mut self,
+            airdrop_id: AirdropId,
+            index: u32,
+            coupon: CouponId,
+            amount: Balance,
+            proof: Vec<MerkleHash>,
+            transfer_to: ReceiverAddress,
+            deadline: Option<BlockNumber>,
+            sign: [u8; 64],
+        ) -> Result<bool, Error> {
+            let (word, bit) = (index / 128, 1u128 << (index % 128));
+            let claims = self.airdrop_claims.get((airdrop_id, word)).unwrap_or_default();
+
+            self.ensure_not_paused(self.redemption_paused.get())
+                .and_then(|_| self.airdrops.get(airdrop_id).ok_or(Error::AirdropNotFound))
+                .and_then(|airdrop| {
+                    // claims are accepted until the airdrop is closed or ended
+                    (!airdrop.closed
+                        && airdrop
+                            .ends
+                            .is_none_or(|ends| Self::env().block_timestamp() < ends))
+                    .then_some(airdrop)
+                    .ok_or(Error::AirdropClosed)
+                })
+                .and_then(|airdrop| {
+                    (claims & bit == 0)
+                        .then_some(airdrop)
+                        .ok_or(Error::CouponAlreadyClaimed)
+                })
+                .and_then(|airdrop| {
+                    // verify coupon leaf by the airdrop root
+                    Self::verify_merkle_proof(
+                        &airdrop.root,
+                        Self::airdrop_leaf(index, &coupon, amount),
+                        &proof,
+                    )
+                    .then_some(airdrop)
+                    .ok_or(Error::InvalidMerkleProof)
+                })
+                .and_then(|airdrop| {
+                    let payload = match deadline {
+                        Some(deadline) => self.check_deadline(deadline).map(|_| {
+                            AirdropClaimPayload {
+                                domain: self.payload_domain(PayloadAction::AirdropClaim),
+                                airdrop: airdrop_id,
+                                index,
+                                coupon,
+                                amount,
+                                receiver: transfer_to,
+                                deadline,
+                            }
+                            .encode()
+                        }),
+                        None => self.legacy_payload(transfer_to),
+                    };
+
+                    // verify signature of the claim payload by coupon key, schemes
+                    // of registered coupons don't apply to airdrop leaves
+                    payload
+                        .and_then(|payload| self.verify_sr25519(&coupon, &payload, &sign))
+                        .map(|_| airdrop)
+                })
+                .and_then(|airdrop| {
+                    (amount <= airdrop.reserved)
+                        .then_some(airdrop)
+                        .ok_or(Error::ContractBalanceNotEnough)
+                })
+                .and_then(|mut airdrop| {
+                    self.payout(airdrop.token, transfer_to, amount)?;
+
+                    // store only the claimed bit & release reservation
+                    self.release(airdrop.token, amount)?;
+                    self.airdrop_claims.insert((airdrop_id, word), &(claims | bit));
+
+                    airdrop.reserved -= amount;
+                    airdrop.claimed += amount;
+                    self.airdrops.insert(airdrop_id, &airdrop);
+                    self.count_redemption(transfer_to);
+
+                    self.env().emit_event(AirdropClaimed {
+                        airdrop: airdrop_id,
+                        coupon,
+                        receiver: transfer_to,
+                        amount,
+                    });
+
+                    Ok(true)
+                })
+        }
+
+        /// Close the airdrop and release its leftover (unclaimed) reservation.
+        /// Allowed only for the `Revoker`.
+        /// Returns: released amount
+        #[ink(message)]
+        pub fn close_airdrop(&mut self, airdrop_id: AirdropId) -> Result<Balance, Error> {
+            self.ensure_role(Role::Revoker)
+                .and_then(|_| self.airdrops.get(airdrop_id).ok_or(Error::AirdropNotFound))
+                .and_then(|airdrop| (!airdrop.closed).then_some(airdrop).ok_or(Error::AirdropClosed))
+                .and_then(|mut airdrop| {
+                    let amount = airdrop.reserved;
+
+                    self.release(airdrop.token, amount)?;
+
+                    airdrop.reserved = 0;
+                    airdrop.closed = true;
+                    self.airdrops.insert(airdrop_id, &airdrop);
+
+                    self.env().emit_event(AirdropClosed {
+                        airdrop: airdrop_id,
+                        amount,
+                    });
+
+                    Ok(amount)
+                })
+        }
+
+        /// Airdrop info with leftover reservation and claimed total
+        #[ink(message)]
+        pub fn airdrop(&self, airdrop_id: AirdropId) -> Option<Airdrop> {
+            self.airdrops.get(airdrop_id)
+        }
+
+        /// Check that airdrop coupon at `index` is already claimed
+        #[ink(message)]
+        pub fn is_airdrop_claimed(&self, airdrop_id: AirdropId, index: u32) -> bool {
+            self.airdrop_claims
+                .get((airdrop_id, index / 128))
+                .is_some_and(|claims| claims & (1u128 << (index % 128)) != 0)
+        }
+
+        /// Set maximum coupons count in one batch request, allowed only for the `Admin`.
+        /// Zero size is rejected, batches and pages couldn't make progress with it.
+        #[ink(message)]
+        pub fn set_max_batch_size(&mut self, size: u32) -> Result<bool, Error> {
+            self.ensure_role(Role::Admin)
+                .and_then(|_| (size > 0).then_some(()).ok_or(Error::InvalidBatchSize))
+                .map(|_| {
+                    self.max_batch_size.set(&size);
+
+                    true
+                })
+        }
+
+        /// Maximum coupons count in one batch request
+        #[ink(message)]
+        pub fn max_batch_size(&self) -> u32 {
+            self.max_batch_size.get().unwrap_or(DEFAULT_MAX_BATCH_SIZE)
+        }
+
+        /// Set chain identifier (genesis hash) of the signed activation payload,
+        /// allowed only for the `Admin`
+        #[ink(message)]
+        pub fn set_chain_id(&mut self, chain_id: Hash) -> Result<bool, Error> {
+            self.ensure_role(Role::Admin).map(|_| {
+                self.chain_id.set(&chain_id);
+
+                true
+            })
+        }
+
+        /// Chain identifier of the signed activation payload
+        #[ink(message)]
+        pub fn chain_id(&self) -> Hash {
+            self.chain_id.get().unwrap_or_default()
+        }
+
+        /// Enable or disable legacy activation signatures without deadline,
+        /// allowed only for the `Admin`
+        #[ink(message)]
+        pub fn set_legacy_signatures(&mut self, enabled: bool) -> Result<bool, Error> {
+            self.ensure_role(Role::Admin).map(|_| {
+                self.legacy_signatures.set(&enabled);
+
+                true
+            })
+        }
+
+        /// Legacy activation signatures without deadline are accepted
+        #[ink(message)]
+        pub fn legacy_signatures(&self) -> bool {
+            self.legacy_signatures.get().unwrap_or(true)
+        }
+
+        /// Set limits of the fee paid to relayers: maximum amount and maximum share
+        /// of the coupon amount in basis points, allowed only for the `Admin`
+        #[ink(message)]
+        pub fn set_relayer_fee_limits(&mut self, max_fee: Balance, max_fee_bps: u16) -> Result<bool, Error> {
+            self.ensure_role(Role::Admin)
+                .and_then(|_| {
+                    (max_fee_bps <= 10_000)
+                        .then_some(())
+                        .ok_or(Error::InvalidFeeLimits)
+                })
+                .map(|_| {
+                    self.relayer_fee_limits.set(&FeeLimits { max_fee, max_fee_bps });
+
+                    true
+                })
+        }
+
+        /// Limits of the fee paid to relayers
+        #[ink(message)]
+        pub fn relayer_fee_limits(&self) -> FeeLimits {
+            self.relayer_fee_limits.get().unwrap_or_default()
+        }
+
+        /// Grant `role` to the `account`, allowed only for the `Admin`
+        #[ink(message)]
+        pub fn grant_role(&mut self, role: Role, account: AccountId) -> Result<bool, Error> {
+            self.ensure_role(Role::Admin).map(|_| {
+                self.roles.insert((role, account), &());
+                self.env().emit_event(RoleGranted { account, role });
+
+                true
+            })
+        }
+
+        /// Revoke `role` from the `account`, allowed only for the `Admin`.
+        /// Roles of the owner can't be revoked.
+        /// Returns: false if the account didn't have the role
+        #[ink(message)]
+        pub fn revoke_role(&mut self, role: Role, account: AccountId) -> Result<bool, Error> {
+            self.ensure_role(Role::Admin)
+                .and_then(|_| {
+                    (account != self.owner)
+                        .then_some(())
+                        .ok_or(Error::OwnerRoleNotRevocable)
+                })
+                .map(|_| {
+                    let revoked = self.roles.take((role, account)).is_some();
+
+                    if revoked {
+                        self.env().emit_event(RoleRevoked { account, role });
+                    }
+
+                    revoked
+                })
+        }
+
+        /// Check that the `account` has the `role`
+        #[ink(message)]
+        pub fn has_role(&self, role: Role, account: AccountId) -> bool {
+            account == self.owner || self.roles.contains((role, account))
+        }
+
+        /// Emergency stop of coupons `issuance` and `redemption`, allowed only for the `Guardian`.
+        /// Burning of coupons, withdrawals and ownership transfer keep working while paused.
+        #[ink(message)]
+        pub fn set_paused(&mut self, issuance: bool, redemption: bool) -> Result<bool, Error> {
+            self.ensure_role(Role::Guardian).map(|_| {
+                self.issuance_paused.set(&issuance);
+                self.redemption_paused.set(&redemption);
+
+                self.env().emit_event(PauseChanged {
+                    guardian: Self::env().caller(),
+                    issuance,
+                    redemption,
+                });
+
+                true
+            })
+        }
+
+        /// Pause state of coupons issuance & redemption, `(issuance, redemption)`
+        #[ink(message)]
+        pub fn paused(&self) -> (bool, bool) {
+            (
+                self.issuance_paused.get().unwrap_or_default(),
+                self.redemption_paused.get().unwrap_or_default(),
+            )
+        }
+
+        /// Transfer contract ownership to another user
+        #[ink(message)]
+        pub fn transfer_ownership(&mut self, account: AccountId) -> Result<bool, Error> {
+            (Self::env().caller() == self.owner)
+                .then(|| {
+                    let previous = self.owner;
+                    self.owner = account;
+
+                    self.env().emit_event(OwnershipTransferred {
+                        previous,
+                        owner: account,
+                    });
+
+                    true
+                })
+                .ok_or(Error::AccessOwner)
+        }
+
+        /// Replace the contract code with the uploaded `code_hash`, allowed only for the owner.
+        /// After upgrade the owner should call `migrate` of the new code.
+        #[ink(message)]
+        pub fn upgrade(&mut self, code_hash: [u8; 32]) -> Result<bool, Error> {
+            (Self::env().caller() == self.owner)
+                .then_some(true)
+                .ok_or(Error::AccessOwner)
+                .and_then(|_| ink::env::set_code_hash(&code_hash).or(Err(Error::UpgradeFailed)))
+                .map(|_| {
+                    self.env().emit_event(CodeUpgraded { code_hash });
+
+                    true
+                })
+        }
+
+        /// Convert storage of older layout versions to the current one, allowed only for the owner
+        /// after `upgrade`. Coupons of version 0 aren't indexed and keep the original balance after
+        /// redemption, so they are converted in batches of `coupons` (up to `max_batch_size`) known
+        /// from the issuer records: active coupons are indexed as active, burned coupons are indexed
+        /// as redeemed with zero balance. An empty batch completes the migration.
+        /// Returns: the storage version after the call
+        #[ink(message)]
+        pub fn migrate(&mut self, coupons: Coupons) -> Result<u32, Error> {
+            (Self::env().caller() == self.owner)
+                .then(|| self.storage_version())
+                .ok_or(Error::AccessOwner)
+                .and_then(|from| {
+                    (from < STORAGE_VERSION)
+                        .then_some(from)
+                        .ok_or(Error::AlreadyMigrated)
+                })
+                .and_then(|from| self.check_batch_size(coupons.len()).map(|_| from))
+                .map(|from| {
+                    // conversion steps of the batch from older versions
+                    if from < 1 {
+                        coupons.iter().for_each(|coupon| self.migrate_coupon_v0(coupon));
+                    }
+
+                    if !coupons.is_empty() {
+                        return from;
+                    }
+
+                    self.version.set(&STORAGE_VERSION);
+
+                    self.env().emit_event(StorageMigrated {
+                        from,
+                        to: STORAGE_VERSION,
+                    });
+
+                    STORAGE_VERSION
+                })
+        }
+
+        /// Remove storage of finished (redeemed, revoked or reclaimed) `coupons` to recover
+        /// storage deposits. The terminal state is kept as a nullifier, so pruned coupons
+        /// can't be added again, and they stay listed in the insertion order. Redemption receipts
+        /// are kept, vesting positions are removed once they are fully claimed.
+        /// Coupons in progress are skipped. Allowed only for the `Revoker`.
+        /// Returns: count of freed storage entries
+        #[ink(message)]
+        pub fn prune_coupons(&mut self, coupons: Coupons) -> Result<u32, Error> {
+            self.ensure_role(Role::Revoker)
+                .and_then(|_| self.check_batch_size(coupons.len()))
+                .map(|_| {
+                    coupons
+                        .into_iter()
+                        .fold(0, |freed, coupon| match self.burned.get(coupon) {
+                            Some(state) => freed + self.prune_coupon(&coupon, state.into()),
+                            None => freed,
+                        })
+                })
+        }
+
+        /// Compare reserved funds of native currency or PSP22 `token` with the contract balance,
+        /// the invariant `reserved <= balance` holds if the contract is solvent. The reserved
+        /// counter is checked by the outstanding amount recomputed from registered coupons
+        /// (with their vesting positions) and airdrops, summed by pages of `max_batch_size`
+        /// positions from the `cursor`. Pass the returned `outstanding`, `cursor` & `coupons` count
+        /// to the next call (`coupons` is none on the first page), so positions of airdrops don't shift
+        /// when coupons are registered between pages. Coupons registered after the first page aren't
+        /// counted, the last page reports if the recomputed amount is `consistent` with `reserved`.
+        /// Coupons of version 0 are counted after `migrate`. Allowed only for the `Admin`.
+        #[ink(message)]
+        pub fn reconcile(
+            &self,
+            token: Option<TokenId>,
+            cursor: u32,
+            outstanding: Balance,
+            coupons: Option<u32>,
+        ) -> Result<Reconciliation, Error> {
+            let coupons_total = coupons.unwrap_or_else(|| self.coupons_total.get().unwrap_or_default());
+            let count = coupons_total.saturating_add(self.airdrops_count.get().unwrap_or_default());
+            let to = count.min(cursor.saturating_add(self.max_batch_size()));
+
+            self.ensure_role(Role::Admin)
+                .and_then(|_| {
+                    // coupons are followed by airdrops
+                    (cursor..to).try_fold(outstanding, |outstanding, position| {
+                        let amount = match position.checked_sub(coupons_total) {
+                            Some(airdrop_id) => self.airdrop_outstanding(airdrop_id, token),
+                            None => self
+                                .coupons_index
+                                .get(position)
+                                .map_or(Ok(0), |coupon| self.coupon_outstanding(&coupon, token))?,
+                        };
+
+                        outstanding.checked_add(amount).ok_or(Error::ArithmeticOverflow)
+                    })
+                })
+                .map(|outstanding| {
+                    let (reserved, balance) = (self.reserved_of(token), self.asset_balance(token));
+                    let cursor = (to < count).then_some(to);
+
+                    Reconciliation {
+                        token,
+                        reserved,
+                        balance,
+                        shortfall: reserved.saturating_sub(balance),
+                        solvent: reserved <= balance,
+                        outstanding,
+                        cursor,
+                        coupons: coupons_total,
+                        consistent: cursor.is_none().then_some(outstanding == reserved),
+                    }
+                })
+        }
+
+        /// Current storage layout version
+        #[ink(message)]
+        pub fn storage_version(&self) -> u32 {
+            self.version.get().unwrap_or_default()
+        }
+
+        #[inline]
+        fn ensure_role(&self, role: Role) -> Result<(), Error> {
+            self.has_role(role, Self::env().caller())
+                .then_some(())
+                .ok_or(Error::MissingRole(role))
+        }
+
+        #[inline]
+        fn ensure_not_paused(&self, paused: Option<bool>) -> Result<(), Error> {
+            // pause flags aren't stored until set
+            (!paused.unwrap_or_default()).then_some(()).ok_or(Error::Paused)
+        }
+
+        #[inline]
+        fn check_relayer_fee(&self, fee: Balance, coupon_amount: Balance) -> Result<(), Error> {
+            let limits = self.relayer_fee_limits();
+
+            // fee is paid from the coupon amount, its share is limited in basis points
+            (fee <= coupon_amount
+                && fee <= limits.max_fee
+                && fee.saturating_mul(10_000) <= coupon_amount.saturating_mul(limits.max_fee_bps.into()))
+            .then_some(())
+            .ok_or(Error::RelayerFeeTooHigh)
+        }
+
+        #[inline]
+        fn check_batch_size(&self, size: usize) -> Result<(), Error> {
+            (size <= self.max_batch_size() as usize)
+                .then_some(())
+                .ok_or(Error::BatchTooLarge)
+        }
+
+        #[inline]
+        fn insert_coupon(
+            &mut self,
+            coupon: &CouponId,
+            amount: Balance,
+            expires: Option<Expiry>,
+            token: Option<TokenId>,
+            scheme: KeyScheme,
+        ) -> Result<Balance, Error> {
+            // pruned coupons keep only the terminal state, so they can't be added again
+            (!self.coupons.contains(coupon) && !self.burned.contains(coupon))
+                .then_some(())
+                .ok_or(Error::CouponAlreadyExists)
+                // reserve balance for payout
+                .and_then(|_| self.reserve(token, amount))
+                .map(|_| {
+                    // insert new coupon to the storage
+                    self.coupons.insert(coupon, &amount);
+
+                    if let Some(expiry) = expires {
+                        self.expires.insert(coupon, &expiry);
+                    }
+
+                    if let Some(token) = token {
+                        self.tokens.insert(coupon, &token);
+                    }
+
+                    if scheme != KeyScheme::Sr25519 {
+                        self.schemes.insert(coupon, &scheme);
+                    }
+
+                    self.issuances.insert(
+                        coupon,
+                        &Issuance {
+                            issuer: Self::env().caller(),
+                            timestamp: Self::env().block_timestamp(),
+                        },
+                    );
+                    self.index_coupon(coupon, CouponStatus::Active);
+
+                    self.env().emit_event(CouponAdded {
+                        coupon: *coupon,
+                        amount,
+                        token,
+                    });
+
+                    amount
+                })
+        }
+
+        #[inline]
+        fn burn_coupon(&mut self, coupon: &CouponId, state: CouponState) -> Result<bool, Error> {
+            self.coupons
+                .get(coupon)
+                .ok_or(Error::CouponNotFound)
+                // coupon in a terminal state is already released
+                .and_then(|amount| self.check_state(coupon).map(|_| amount))
+                .and_then(|amount| {
+                    self.release_campaign(
+                        self.coupon_campaigns.get(coupon),
+                        amount,
+                        CampaignRelease::Burned,
+                    )?;
+
+                    // cancellation of funds reservation
+                    self.release(self.tokens.get(coupon), amount)?;
+                    // mark coupon as revoked, expired or cancelled
+                    self.finish_coupon(coupon, state);
+
+                    Ok(true)
+                })
+        }
+
+        #[inline]
+        fn spend_coupon(
+            &mut self,
+            coupon: &CouponId,
+            receiver: ReceiverAddress,
+            amount: Balance,
+            fee: Balance,
+        ) -> Result<Balance, Error> {
+            self.coupons
+                .get(coupon)
+                .ok_or(Error::CouponNotFound)
+                .and_then(|coupon_amount| {
+                    // spend coupon balance & release reservation
+                    let rest_amount = coupon_amount
+                        .checked_sub(amount)
+                        .ok_or(Error::ArithmeticOverflow)?;
+
+                    // reservation of the vesting coupon moves to the vesting position
+                    if !self.vesting_terms.contains(coupon) {
+                        self.release_campaign(
+                            self.coupon_campaigns.get(coupon),
+                            amount,
+                            CampaignRelease::Redeemed,
+                        )?;
+
+                        self.release(self.tokens.get(coupon), amount)?;
+                    }
+
+                    self.coupons.insert(coupon, &rest_amount);
+
+                    if let Some(mut uses) = self.multi_use.get(coupon) {
+                        uses.redemptions += 1;
+                        self.multi_use.insert(coupon, &uses);
+                        self.multi_use_claims.insert((*coupon, receiver), &());
+                    }
+
+                    // keep receipt of the redemption
+                    let redeemed = self.receipts.get(coupon).map_or(0, |receipt| receipt.total);
+
+                    self.receipts.insert(
+                        coupon,
+                        &Receipt {
+                            receiver,
+                            // relayer fee is paid from the spent amount
+                            amount: amount - fee,
+                            fee,
+                            total: redeemed + amount,
+                            block: Self::env().block_number(),
+                            timestamp: Self::env().block_timestamp(),
+                        },
+                    );
+                    self.count_redemption(receiver);
+
+                    // mark spent coupon as redeemed
+                    if rest_amount == 0 {
+                        self.finish_coupon(coupon, CouponState::Redeemed);
+                    }
+
+                    Ok(rest_amount)
+                })
+        }
+
+        #[inline]
+        fn index_coupon(&mut self, coupon: &CouponId, status: CouponStatus) {
+            let total = self.coupons_total.get().unwrap_or_default();
+
+            self.coupons_index.insert(total, coupon);
+            self.coupons_total.set(&(total + 1));
+            self.push_status(coupon, status);
+        }
+
+        #[inline]
+        fn migrate_coupon_v0(&mut self, coupon: &CouponId) {
+            // coupons of version 0 aren't indexed, the legacy burned flag decodes as redeemed
+            if self.coupons.contains(coupon) && !self.status_positions.contains(coupon) {
+                match self.burned.get(coupon) {
+                    Some(state) => {
+                        // burned coupons of version 0 keep the released balance
+                        self.coupons.insert(coupon, &0);
+                        self.index_coupon(coupon, state.into());
+                    }
+                    None => self.index_coupon(coupon, CouponStatus::Active),
+                }
+            }
+        }
+
+        #[inline]
+        fn finish_coupon(&mut self, coupon: &CouponId, state: CouponState) {
+            self.burned.insert(coupon, &state);
+
+            // coupons registered before the index aren't listed as active
+            if self.status_positions.contains(coupon) {
+                self.remove_status(coupon, CouponStatus::Active);
+                self.push_status(coupon, state.into());
+            }
+        }
+
+        #[inline]
+        fn prune_coupon(&mut self, coupon: &CouponId, status: CouponStatus) -> u32 {
+            let listed = self.status_positions.contains(coupon);
+            // vesting position is finished when the vested amount is claimed and nothing is left to vest
+            let vesting_finished = self.vestings.get(coupon).is_some_and(|vesting| {
+                (vesting.claimed == vesting.total || vesting.cancelled.is_some())
+                    && Self::vested_amount(&vesting, Self::env().block_timestamp()) == Ok(vesting.claimed)
+            });
+            let freed = [
+                self.coupons.take(coupon).is_some(),
+                self.expires.take(coupon).is_some(),
+                self.nonces.take(coupon).is_some(),
+                self.tokens.take(coupon).is_some(),
+                self.coupon_campaigns.take(coupon).is_some(),
+                self.issuances.take(coupon).is_some(),
+                self.schemes.take(coupon).is_some(),
+                self.bound_receivers.take(coupon).is_some(),
+                self.multi_use.take(coupon).is_some(),
+                self.vesting_terms.take(coupon).is_some(),
+                vesting_finished && self.vestings.take(coupon).is_some(),
+            ];
+
+            // the status list entry and the coupon position are freed
+            if listed {
+                self.remove_status(coupon, status);
+                self.pruned_total
+                    .set(&(self.pruned_total.get().unwrap_or_default() + 1));
+            }
+
+            freed.iter().filter(|freed| **freed).count() as u32 + if listed { 2 } else { 0 }
+        }
+
+        #[inline]
+        fn move_coupon(&mut self, coupon: &CouponId, new_coupon: &CouponId) {
+            // reservation & campaign totals are kept, the coupon entries are moved to the new key
+            Self::move_entry(&mut self.coupons, coupon, new_coupon);
+            Self::move_entry(&mut self.expires, coupon, new_coupon);
+            Self::move_entry(&mut self.nonces, coupon, new_coupon);
+            Self::move_entry(&mut self.tokens, coupon, new_coupon);
+            Self::move_entry(&mut self.coupon_campaigns, coupon, new_coupon);
+            Self::move_entry(&mut self.issuances, coupon, new_coupon);
+            Self::move_entry(&mut self.receipts, coupon, new_coupon);
+            Self::move_entry(&mut self.schemes, coupon, new_coupon);
+            Self::move_entry(&mut self.bound_receivers, coupon, new_coupon);
+            Self::move_entry(&mut self.vesting_terms, coupon, new_coupon);
+
+            // campaign keeps the old key, it's resolved by the rekeys chain
+            self.rekeys.insert(coupon, new_coupon);
+            self.finish_coupon(coupon, CouponState::Rekeyed);
+            self.index_coupon(new_coupon, CouponStatus::Active);
+        }
+
+        #[inline]
+        fn move_entry<V: Packed + scale::EncodeLike, K: StorageKey>(
+            mapping: &mut Mapping<CouponId, V, K>,
+            coupon: &CouponId,
+            new_coupon: &CouponId,
+        ) {
+            if let Some(value) = mapping.take(coupon) {
+                mapping.insert(new_coupon, &value);
+            }
+        }
+
+        #[inline]
+        fn rekeyed_coupon(&self, mut coupon: CouponId) -> CouponId {
+            while let Some(new_coupon) = self.rekeys.get(coupon) {
+                coupon = new_coupon;
+            }
+
+            coupon
+        }
+
+        #[inline]
+        fn push_status(&mut self, coupon: &CouponId, status: CouponStatus) {
+            let count = self.status_counts.get(status).unwrap_or_default();
+
+            self.status_coupons.insert((status, count), coupon);
+            self.status_positions.insert(coupon, &count);
+            self.status_counts.insert(status, &(count + 1));
+        }
+
+        #[inline]
+        fn remove_status(&mut self, coupon: &CouponId, status: CouponStatus) {
+            if let Some(position) = self.status_positions.take(coupon) {
+                let last = self
+                    .status_counts
+                    .get(status)
+                    .unwrap_or_default()
+                    .saturating_sub(1);
+
+                // the last coupon of the list takes the removed position
+                if let Some(moved) = self
+                    .status_coupons
+                    .take((status, last))
+                    .filter(|_| position != last)
+                {
+                    self.status_coupons.insert((status, position), &moved);
+                    self.status_positions.insert(moved, &position);
+                }
+
+                self.status_counts.insert(status, &last);
+            }
+        }
+
+        #[inline]
+        fn airdrop_leaf(index: u32, coupon: &CouponId, amount: Balance) -> MerkleHash {
+            let mut leaf = MerkleHash::default();
+            ink::env::hash_encoded::<Blake2x256, _>(&(index, coupon, amount), &mut leaf);
+            leaf
+        }
+
+        #[inline]
+        fn merkle_parent(left: &MerkleHash, right: &MerkleHash) -> MerkleHash {
+            let mut parent = MerkleHash::default();
+            // children are sorted, so proof doesn't need the positions
+            ink::env::hash_encoded::<Blake2x256, _>(&(left.min(right), left.max(right)), &mut parent);
+            parent
+        }
+
+        #[inline]
+        fn verify_merkle_proof(root: &MerkleHash, leaf: MerkleHash, proof: &[MerkleHash]) -> bool {
+            proof
+                .iter()
+                .fold(leaf, |node, sibling| Self::merkle_parent(&node, sibling))
+                == *root
+        }
+
+        #[inline]
+        fn count_redemption(&mut self, receiver: ReceiverAddress) {
+            self.receiver_redemptions
+                .insert(receiver, &(self.receiver_redemptions(receiver) + 1));
+        }
+
+        #[inline]
+        fn update_campaign(
+            &mut self,
+            campaign_id: CampaignId,
+            update: impl FnOnce(&mut Campaign) -> Result<(), Error>,
+        ) -> Result<(), Error> {
+            if let Some(mut campaign) = self.campaigns.get(campaign_id) {
+                // campaign isn't changed if the update fails
+                update(&mut campaign)?;
+                self.campaigns.insert(campaign_id, &campaign);
+            }
+
+            Ok(())
+        }
+
+        #[inline]
+        fn release_campaign(
+            &mut self,
+            campaign: Option<CampaignId>,
+            amount: Balance,
+            release: CampaignRelease,
+        ) -> Result<(), Error> {
+            match campaign {
+                Some(campaign_id) => self.update_campaign(campaign_id, |campaign| {
+                    campaign.reserved = campaign
+                        .reserved
+                        .checked_sub(amount)
+                        .ok_or(Error::ArithmeticOverflow)?;
+
+                    let total = match release {
+                        CampaignRelease::Redeemed => &mut campaign.redeemed,
+                        CampaignRelease::Burned => &mut campaign.burned,
+                    };
+                    *total = total.checked_add(amount).ok_or(Error::ArithmeticOverflow)?;
+
+                    Ok(())
+                }),
+                None => Ok(()),
+            }
+        }
+
+        #[inline]
+        fn check_campaign(&self, coupon: &CouponId) -> Result<(), Error> {
+            match self
+                .coupon_campaigns
+                .get(coupon)
+                .and_then(|id| self.campaigns.get(id))
+            {
+                Some(campaign) if campaign.closed => Err(Error::CampaignClosed),
+                Some(campaign) => {
+                    let now = Self::env().block_timestamp();
+
+                    // redemption is allowed only in the campaign period
+                    (campaign.starts.is_none_or(|starts| now >= starts)
+                        && campaign.ends.is_none_or(|ends| now < ends))
+                    .then_some(())
+                    .ok_or(Error::CampaignNotActive)
+                }
+                None => Ok(()),
+            }
+        }
+
+        #[inline]
+        fn check_state(&self, coupon: &CouponId) -> Result<(), Error> {
+            // check that coupon isn't redeemed, revoked or reclaimed
+            match self.burned.get(coupon) {
+                None => Ok(()),
+                Some(CouponState::Redeemed) => Err(Error::CouponAlreadyRedeemed),
+                Some(CouponState::Revoked) => Err(Error::CouponRevoked),
+                Some(CouponState::Expired) => Err(Error::CouponExpired),
+                Some(CouponState::Rekeyed) => Err(Error::CouponRekeyed),
+                Some(CouponState::Cancelled) => Err(Error::CouponCancelled),
+            }
+        }
+
+        #[inline]
+        fn active_coupon(&self, coupon: &CouponId, receiver: ReceiverAddress) -> Result<Balance, Error> {
+            // terminal state is checked first, it's kept for pruned coupons
+            self.check_state(coupon)
+                .and_then(|_| self.coupons.get(coupon).ok_or(Error::InvalidParseCoupon))
+                .and_then(|coupon_amount| {
+                    // check that coupon isn't expired
+                    (!self.is_expired(coupon))
+                        .then_some(coupon_amount)
+                        .ok_or(Error::CouponExpired)
+                })
+                .and_then(|coupon_amount| self.check_campaign(coupon).map(|_| coupon_amount))
+                .and_then(|coupon_amount| {
+                    // bound coupon pays only to the allowed receivers
+                    self.bound_receivers
+                        .get(coupon)
+                        .is_none_or(|receivers| receivers.contains(&receiver))
+                        .then_some(coupon_amount)
+                        .ok_or(Error::ReceiverNotAllowed)
+                })
+        }
+
+        #[inline]
+        fn start_vesting(
+            &mut self,
+            coupon: &CouponId,
+            receiver: ReceiverAddress,
+            amount: Balance,
+            terms: VestingTerms,
+        ) -> Result<Balance, Error> {
+            // reservation of the spent coupon is kept for the vesting position
+            self.vestings.insert(
+                coupon,
+                &Vesting {
+                    receiver,
+                    token: self.tokens.get(coupon),
+                    total: amount,
+                    claimed: 0,
+                    start: Self::env().block_timestamp(),
+                    terms,
+                    cancelled: None,
+                    campaign: self.coupon_campaigns.get(coupon),
+                },
+            );
+
+            Ok(amount)
+        }
+
+        #[inline]
+        fn vested_amount(vesting: &Vesting, now: Timestamp) -> Result<Balance, Error> {
+            // vesting stops at the time of cancellation
+            let elapsed = vesting
+                .cancelled
+                .unwrap_or(now)
+                .min(now)
+                .saturating_sub(vesting.start);
+
+            match elapsed {
+                elapsed if elapsed < vesting.terms.cliff => Ok(0),
+                elapsed if elapsed >= vesting.terms.duration => Ok(vesting.total),
+                elapsed => vesting
+                    .total
+                    .checked_mul(elapsed.into())
+                    .map(|amount| amount / Balance::from(vesting.terms.duration))
+                    .ok_or(Error::ArithmeticOverflow),
+            }
+        }
+
+        #[inline]
+        fn coupon_outstanding(&self, coupon: &CouponId, token: Option<TokenId>) -> Result<Balance, Error> {
+            // balance of the finished coupon is released
+            let balance = if self.burned.contains(coupon) || self.tokens.get(coupon) != token {
+                0
+            } else {
+                self.coupons.get(coupon).unwrap_or_default()
+            };
+
+            // vested part of the cancelled position is reserved until claimed
+            let vesting = match self.vestings.get(coupon).filter(|vesting| vesting.token == token) {
+                Some(vesting) if vesting.cancelled.is_some() => {
+                    Self::vested_amount(&vesting, Self::env().block_timestamp())? - vesting.claimed
+                }
+                Some(vesting) => vesting.total - vesting.claimed,
+                None => 0,
+            };
+
+            balance.checked_add(vesting).ok_or(Error::ArithmeticOverflow)
+        }
+
+        #[inline]
+        fn airdrop_outstanding(&self, airdrop_id: AirdropId, token: Option<TokenId>) -> Balance {
+            self.airdrops
+                .get(airdrop_id)
+                .filter(|airdrop| airdrop.token == token)
+                .map_or(0, |airdrop| airdrop.reserved)
+        }
+
+        #[inline]
+        fn activation_amount(
+            &self,
+            coupon: &CouponId,
+            receiver: ReceiverAddress,
+            coupon_amount: Balance,
+        ) -> Result<Balance, Error> {
+            match self.multi_use.get(coupon) {
+                // multi-use coupon pays its amount once per receiver
+                Some(uses) => (!self.multi_use_claims.contains((*coupon, receiver)))
+                    .then(|| uses.amount.min(coupon_amount))
+                    .ok_or(Error::CouponAlreadyClaimed),
+                None => Ok(coupon_amount),
+            }
+        }
+
+        #[inline]
+        fn payload_domain(&self, action: PayloadAction) -> PayloadDomain {
+            PayloadDomain {
+                version: PAYLOAD_VERSION,
+                action,
+                contract: Self::env().account_id(),
+                chain: self.chain_id(),
+            }
+        }
+
+        #[inline]
+        fn verify_signature(&self, coupon: &CouponId, payload: &[u8], sign: &[u8; 64]) -> Result<(), Error> {
+            match self.coupon_scheme(*coupon) {
+                KeyScheme::Sr25519 => self.verify_sr25519(coupon, payload, sign),
+                KeyScheme::Ed25519 => self.verify_ed25519(coupon, payload, sign),
+                KeyScheme::Ecdsa => self.verify_ecdsa(coupon, payload, sign),
+            }
+        }
+
+        #[inline]
+        fn activate(
+            &mut self,
+            transfer_to: ReceiverAddress,
+            coupon: CouponId,
+            deadline: Option<BlockNumber>,
+            sign: [u8; 64],
+        ) -> Result<bool, Error> {
+            self.ensure_not_paused(self.redemption_paused.get())
+                .and_then(|_| self.active_coupon(&coupon, transfer_to))
+                .and_then(|coupon_amount| self.activation_amount(&coupon, transfer_to, coupon_amount))
+                .and_then(|coupon_amount| {
+                    // verify signature of the activation payload by coupon key
+                    self.activation_payload(&coupon, transfer_to, deadline)
+                        .and_then(|payload| self.verify_signature(&coupon, &payload, &sign))
+                        .map(|_| coupon_amount)
+                })
+                .and_then(|coupon_amount| match self.vesting_terms.get(coupon) {
+                    // vesting coupon turns into the vesting position of the receiver
+                    Some(terms) => self.start_vesting(&coupon, transfer_to, coupon_amount, terms),
+                    None => self.payout(self.tokens.get(coupon), transfer_to, coupon_amount),
+                })
+                .and_then(|coupon_amount| {
+                    self.spend_coupon(&coupon, transfer_to, coupon_amount, 0)?;
+                    self.env().emit_event(CouponActivated {
+                        coupon,
+                        receiver: transfer_to,
+                        amount: coupon_amount,
+                    });
+
+                    Ok(true)
+                })
+        }
+
+        #[inline]
+        fn activation_payload(
+            &self,
+            coupon: &CouponId,
+            receiver: ReceiverAddress,
+            deadline: Option<BlockNumber>,
+        ) -> Result<Vec<u8>, Error> {
+            match deadline {
+                Some(deadline) => self.check_deadline(deadline).map(|_| {
+                    ActivationPayload {
+                        domain: self.payload_domain(PayloadAction::Activation),
+                        coupon: *coupon,
+                        receiver,
+                        deadline,
+                    }
+                    .encode()
+                }),
+                None => self.legacy_payload(receiver),
+            }
+        }
+
+        #[inline]
+        fn legacy_payload(&self, receiver: ReceiverAddress) -> Result<Vec<u8>, Error> {
+            // legacy payload is the receiver address only
+            self.legacy_signatures()
+                .then(|| AsRef::<[u8]>::as_ref(&receiver).to_vec())
+                .ok_or(Error::LegacySignatureDisabled)
+        }
+
+        #[inline]
+        fn check_deadline(&self, deadline: BlockNumber) -> Result<(), Error> {
+            (Self::env().block_number() <= deadline)
+                .then_some(())
+                .ok_or(Error::SignatureExpired)
+        }
+
+        #[inline]
+        fn verify_sr25519(&self, coupon: &CouponId, payload: &[u8], sign: &[u8; 64]) -> Result<(), Error> {
+            // parsing & cast coupon key
+            let public_key = PublicKey::from_bytes(coupon.as_ref()).or(Err(Error::InvalidParseCoupon))?;
+            // parsing & cast signature
+            let signature = Signature::from_bytes(sign).or(Err(Error::InvalidParseCouponSignature))?;
+            let context = signing_context(Self::env().account_id().as_ref());
+
+            // verify signature payload with context by coupon key
+            public_key
+                .verify(context.bytes(payload), &signature)
+                .or(Err(Error::VerifySignatureFailed))
+        }
+
+        #[inline]
+        fn verify_ed25519(&self, coupon: &CouponId, payload: &[u8], sign: &[u8; 64]) -> Result<(), Error> {
+            let public_key =
+                ed25519_compact::PublicKey::from_slice(coupon.as_ref()).or(Err(Error::InvalidParseCoupon))?;
+            let signature =
+                ed25519_compact::Signature::from_slice(sign).or(Err(Error::InvalidParseCouponSignature))?;
+            // contract id is the signing context
+            let message = [Self::env().account_id().as_ref(), payload].concat();
+
+            public_key
+                .verify(&message, &signature)
+                .or(Err(Error::VerifySignatureFailed))
+        }
+
+        #[inline]
+        fn verify_ecdsa(&self, coupon: &CouponId, payload: &[u8], sign: &[u8; 64]) -> Result<(), Error> {
+            // EIP-191 personal message of the contract address followed by the payload
+            let message = [Self::env().account_id().as_ref(), payload].concat();
+            let prefix = format!("\x19Ethereum Signed Message:\n{}", message.len());
+
+            let mut message_hash = <Keccak256 as HashOutput>::Type::default();
+            ink::env::hash_bytes::<Keccak256>(&[prefix.as_bytes(), &message].concat(), &mut message_hash);
+
+            // coupon is the Ethereum address of the key padded with zeros
+            let (address, padding) = AsRef::<[u8]>::as_ref(coupon).split_at(20);
+
+            // recovery id isn't signed, so both candidate keys are recovered
+            (0..2u8)
+                .any(|recovery_id| {
+                    let mut signature = [recovery_id; 65];
+                    signature[..64].copy_from_slice(sign);
+
+                    let mut public_key = [0; 33];
+                    let mut recovered = [0; 20];
+
+                    ink::env::ecdsa_recover(&signature, &message_hash, &mut public_key).is_ok()
+                        && ink::env::ecdsa_to_eth_address(&public_key, &mut recovered).is_ok()
+                        && recovered == address
+                })
+                .then_some(())
+                .filter(|_| padding.iter().all(|byte| *byte == 0))
+                .ok_or(Error::VerifySignatureFailed)
+        }
+
+        #[inline]
+        fn payback_not_reserved(&mut self, token: Option<TokenId>) -> Result<bool, Error> {
+            self.ensure_role(Role::Treasurer)
+                .map(|_| self.rest_balance_of(token))
+                .and_then(|rest_balance| self.payout(token, self.owner, rest_balance))
+                .map(|amount| {
+                    self.env().emit_event(FundsWithdrawn {
+                        receiver: self.owner,
+                        amount,
+                        token,
+                    });
+
+                    true
+                })
+        }
+
+        #[inline]
+        fn payout(
+            &mut self,
+            token: Option<TokenId>,
+            transfer_to: ReceiverAddress,
+            amount: Balance,
+        ) -> Result<Balance, Error> {
+            // check that contract balance is enough for transfer
+            (amount <= self.asset_balance(token))
+                .then_some(amount)
+                .ok_or(Error::ContractBalanceNotEnough)
+                .and_then(|amount| {
+                    // transfer funds to verified receiver
+                    match token {
+                        Some(token) => self.transfer_token(token, transfer_to, amount),
+                        None => self
+                            .env()
+                            .transfer(transfer_to, amount)
+                            .map_err(|_| Error::TransferFailed),
+                    }
+                    .map(|_| amount)
+                })
+        }
+
+        #[inline]
+        fn transfer_token(
+            &self,
+            token: TokenId,
+            transfer_to: ReceiverAddress,
+            amount: Balance,
+        ) -> Result<(), Error> {
+            Self::transfer_result(<<Self as TokenCalls>::Tokens as Psp22>::transfer(
+                token,
+                transfer_to,
+                amount,
+            ))
+        }
+
+        #[inline]
+        fn transfer_result(result: CallResult<Result<(), PSP22Error>>) -> Result<(), Error> {
+            // PSP22 error returned by the token as well as a failed call are failed transfers
+            match result {
+                Ok(Ok(Ok(()))) => Ok(()),
+                Ok(Ok(Err(_))) | Ok(Err(_)) | Err(_) => Err(Error::TransferFailed),
+            }
+        }
+
+        #[inline]
+        fn asset_balance(&self, token: Option<TokenId>) -> Balance {
+            match token {
+                Some(token) => self.token_balance(token),
+                None => Self::env().balance(),
+            }
+        }
+
+        #[inline]
+        fn token_balance(&self, token: TokenId) -> Balance {
+            // balance of the failed call is unknown, nothing can be paid out
+            <<Self as TokenCalls>::Tokens as Psp22>::balance_of(token, Self::env().account_id())
+                .map_or(0, |result| result.unwrap_or_default())
+        }
+
+        #[inline]
+        fn reserved_of(&self, token: Option<TokenId>) -> Balance {
+            match token {
+                Some(token) => self.reserved_tokens.get(token).unwrap_or_default(),
+                None => self.reserved,
+            }
+        }
+
+        #[inline]
+        fn reserve(&mut self, token: Option<TokenId>, amount: Balance) -> Result<(), Error> {
+            self.reserved_of(token)
+                .checked_add(amount)
+                .ok_or(Error::ArithmeticOverflow)
+                .map(|reserved| self.set_reserved(token, reserved))
+        }
+
+        #[inline]
+        fn release(&mut self, token: Option<TokenId>, amount: Balance) -> Result<(), Error> {
+            self.reserved_of(token)
+                .checked_sub(amount)
+                .ok_or(Error::ArithmeticOverflow)
+                .map(|reserved| self.set_reserved(token, reserved))
+        }
+
+        #[inline]
+        fn set_reserved(&mut self, token: Option<TokenId>, reserved: Balance) {
+            if let Some(token) = token {
+                self.reserved_tokens.insert(token, &reserved);
+            } else {
+                self.reserved = reserved;
+            }
+        }
+
+        #[inline]
+        fn is_expired(&self, coupon: &CouponId) -> bool {
+            self.expires
+                .get(coupon)
+                .map(|expiry| self.has_passed(&expiry))
+                .unwrap_or(false)
+        }
+
+        #[inline]
+        fn has_passed(&self, expiry: &Expiry) -> bool {
+            match expiry {
+                Expiry::Timestamp(timestamp) => Self::env().block_timestamp() >= *timestamp,
+                Expiry::BlockNumber(block) => Self::env().block_number() >= *block,
+            }
+        }
+
+        #[inline]
+        fn check_expiry(&self, expires: Option<Expiry>) -> Result<(), Error> {
+            // coupons can't be issued already expired
+            match expires {
+                Some(expiry) if self.has_passed(&expiry) => Err(Error::CouponExpired),
+                _ => Ok(()),
+            }
+        }
+
+        #[inline]
+        fn rest_balance(&self) -> Balance {
+            self.rest_balance_of(None)
+        }
+
+        #[inline]
+        fn rest_balance_of(&self, token: Option<TokenId>) -> Balance {
+            // reserved funds may exceed the balance, see `reconcile`
+            self.asset_balance(token).saturating_sub(self.reserved_of(token))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        use schnorrkel::{Keypair, MiniSecretKey};
+        use sp_core::{ecdsa, ed25519, hashing::keccak_256, Pair};
+        use AccountId;
+
+        // PSP22 tokens are mocked, the off-chain environment can't call contracts
+        pub mod psp22 {
+            use super::*;
+            use std::cell::RefCell;
+            use std::collections::BTreeMap;
+
+            thread_local! {
+                static BALANCES: RefCell<BTreeMap<(TokenId, AccountId), Balance>> =
+                    const { RefCell::new(BTreeMap::new()) };
+                static FROZEN: RefCell<BTreeMap<TokenId, bool>> = const { RefCell::new(BTreeMap::new()) };
+            }
+
+            pub fn freeze(token: TokenId, frozen: bool) {
+                FROZEN.with(|tokens| tokens.borrow_mut().insert(token, frozen));
+            }
+
+            pub fn balance_of(token: TokenId, owner: AccountId) -> Balance {
+                BALANCES.with(|balances| {
+                    balances
+                        .borrow()
+                        .get(&(token, owner))
+                        .copied()
+                        .unwrap_or_default()
+                })
+            }
+
+            pub fn mint(token: TokenId, owner: AccountId, amount: Balance) {
+                BALANCES.with(|balances| *balances.borrow_mut().entry((token, owner)).or_default() += amount);
+            }
+
+            pub fn transfer(
+                token: TokenId,
+                from: AccountId,
+                to: AccountId,
+                amount: Balance,
+            ) -> Result<(), PSP22Error> {
+                let balance = balance_of(token, from);
+
+                if FROZEN.with(|tokens| tokens.borrow().get(&token).copied().unwrap_or_default()) {
+                    return Err(PSP22Error::Custom(String::from("frozen")));
+                }
+
+                (balance >= amount)
+                    .then_some(())
+                    .ok_or(PSP22Error::InsufficientBalance)
+                    .map(|_| {
+                        BALANCES
+                            .with(|balances| balances.borrow_mut().insert((token, from), balance - amount));
+                        mint(token, to, amount);
+                    })
+            }
+
+            /// PSP22 calls of the contract with mocked balances
+            pub struct Psp22Mock;
+
+            impl Psp22 for Psp22Mock {
+                fn transfer(
+                    token: TokenId,
+                    to: AccountId,
+                    amount: Balance,
+                ) -> CallResult<Result<(), PSP22Error>> {
+                    Ok(Ok(transfer(token, contract_id(), to, amount)))
+                }
+
+                fn balance_of(token: TokenId, owner: AccountId) -> CallResult<Balance> {
+                    Ok(Ok(balance_of(token, owner)))
+                }
+            }
+
+            impl TokenCalls for Ocex {
+                type Tokens = Psp22Mock;
+            }
+        }
+
+        #[ink::test]
+        // Simple check for coupon insertion
+        // And coupon activation with it's secret key
+        fn insert_coupon_activation() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            // setup sender (by default `alice` also publisher and can add coupons)
+            set_sender(accounts.alice);
+
+            // setup coupon
+            let (coupon_one, coupon_signer) = get_coupon();
+            let coupon_amount: u128 = 500;
+
+            // adding one coupon with target amount
+            assert_eq!(contract.add_coupon(coupon_one, coupon_amount), Ok(coupon_amount));
+
+            // Check funds are reserved
+            set_sender(accounts.alice);
+            assert_eq!(contract.available_balance(), 500);
+
+            // Check methods by client
+            set_sender(accounts.eve);
+            set_balance(accounts.eve, 0);
+
+            // check added coupon & amount;
+            assert_eq!(contract.check_coupon(coupon_one), (true, coupon_amount));
+
+            // Activate coupon
+            let context = signing_context(contract_id().as_ref());
+            let signature = coupon_signer.sign(context.bytes(accounts.eve.as_ref()));
+
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
+                Ok(true)
+            );
+
+            // Coupon activated - amount transfered to caller
+            assert_eq!(get_balance(accounts.eve), 500);
+
+            // Check available funds (not reserved) and withdraw
+            set_sender(accounts.alice);
+            assert_eq!(contract.available_balance(), 500);
+
+            // transfer the rest of funds back to the owner
+            assert_eq!(contract.payback_not_reserved_funds(), Ok(true));
+        }
+
+        #[ink::test]
+        fn insert_coupons() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            // setup sender, (by default `alice` also publisher and can add coupons)
+            set_sender(accounts.alice);
+
+            // setup coupon
+            let (coupon_one, _) = get_coupon();
+            let coupon_amount: u128 = 500;
+
+            let test_coupons =
+                vec![coupon_one, accounts.charlie, accounts.django, accounts.frank, accounts.bob];
+
+            // insert multiple coupons with total amount
+            // that exceeds the contract spare liquidity
+            assert_eq!(
+                contract.add_coupons(test_coupons, coupon_amount, None, None, KeyScheme::Sr25519),
+                Ok(CouponsResult {
+                    accepted: vec![coupon_one, accounts.charlie],
+                    declined: vec![accounts.django, accounts.frank, accounts.bob]
+                })
+            );
+
+            // Check that funds are reserved and withdrawal is restricted
+            assert_eq!(contract.available_balance(), 0);
+
+            // Burn inserted coupons
+            assert_eq!(
+                contract.burn_coupons(vec![coupon_one, accounts.charlie]),
+                Ok(CouponsResult {
+                    accepted: vec![coupon_one, accounts.charlie],
+                    declined: vec![]
+                })
+            );
+
+            assert_eq!(contract.available_balance(), 1000);
+
+            // batch size is limited
+            assert_eq!(contract.set_max_batch_size(0), Err(Error::InvalidBatchSize));
+            assert_eq!(contract.set_max_batch_size(2), Ok(true));
+            assert_eq!(
+                contract.add_coupons(
+                    vec![accounts.django, accounts.frank, accounts.bob],
+                    100,
+                    None,
+                    None,
+                    KeyScheme::Sr25519
+                ),
+                Err(Error::BatchTooLarge)
+            );
+        }
+
+        #[ink::test]
+        fn insert_coupons_with_amounts() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, _) = get_coupon();
+            assert_eq!(contract.add_coupon(coupon_one, 100), Ok(100));
+
+            // tiered coupons with a duplicate & not enough liquidity for the last tier
+            assert_eq!(
+                contract.add_coupons_with_amounts(
+                    vec![
+                        (accounts.bob, 500),
+                        (coupon_one, 50),
+                        (accounts.charlie, 300),
+                        (accounts.django, 200),
+                        (accounts.frank, 100),
+                    ],
+                    None,
+                    None,
+                    KeyScheme::Sr25519
+                ),
+                Ok(CouponsAmountsResult {
+                    accepted: vec![accounts.bob, accounts.charlie, accounts.frank],
+                    declined: vec![
+                        (coupon_one, Error::CouponAlreadyExists),
+                        (accounts.django, Error::ContractBalanceNotEnough),
+                    ],
+                })
+            );
+
+            assert_eq!(contract.check_coupon(accounts.charlie), (true, 300));
+            assert_eq!(contract.available_balance(), 0);
+        }
+
+        #[ink::test]
+        fn campaign_coupons() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            // coupon out of campaigns
+            assert_eq!(contract.add_coupon(accounts.bob, 100), Ok(100));
+
+            // campaign needs a budget and a period starting before the end
+            assert_eq!(
+                contract.create_campaign(String::from("Spring"), 0, None, Some(10), Some(100)),
+                Err(Error::InvalidCampaignTerms)
+            );
+            assert_eq!(
+                contract.create_campaign(String::from("Spring"), 600, None, Some(100), Some(100)),
+                Err(Error::InvalidCampaignTerms)
+            );
+
+            // campaign with redemption period
+            assert_eq!(
+                contract.create_campaign(String::from("Spring"), 600, None, Some(10), Some(100)),
+                Ok(0)
+            );
+
+            let (coupon_one, coupon_signer) = get_coupon();
+            let (coupon_two, _) = get_coupon();
+
+            assert_eq!(
+                contract.add_campaign_coupons(
+                    0,
+                    vec![(coupon_one, 300), (coupon_two, 200), (accounts.charlie, 200)],
+                    None,
+                    KeyScheme::Sr25519
+                ),
+                Ok(CouponsAmountsResult {
+                    accepted: vec![coupon_one, coupon_two],
+                    declined: vec![(accounts.charlie, Error::CampaignBudgetExceeded)],
+                })
+            );
+            assert_eq!(contract.coupon_campaign(coupon_one), Some(0));
+
+            // redemption before the campaign start
+            set_sender(accounts.eve);
+            let context = signing_context(contract_id().as_ref());
+            let signature = coupon_signer.sign(context.bytes(accounts.eve.as_ref()));
+
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
+                Err(Error::CampaignNotActive)
+            );
+
+            set_block_timestamp(10);
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
+                Ok(true)
+            );
+
+            // close campaign, only the campaign reservation is released
+            set_sender(accounts.alice);
+            assert_eq!(contract.close_campaign(0), Ok(0));
+            assert_eq!(contract.check_coupon(coupon_two), (false, 200));
+            assert_eq!(contract.check_coupon(accounts.bob), (true, 100));
+            assert_eq!(contract.available_balance(), 600);
+
+            let campaign = contract.campaign(0).expect("Campaign not found");
+            assert_eq!(
+                (
+                    campaign.issued,
+                    campaign.redeemed,
+                    campaign.burned,
+                    campaign.reserved
+                ),
+                (500, 300, 200, 0)
+            );
+            assert!(campaign.closed);
+
+            // closed campaign doesn't accept coupons
+            assert_eq!(
+                contract.add_campaign_coupons(0, vec![(accounts.charlie, 100)], None, KeyScheme::Sr25519),
+                Err(Error::CampaignClosed)
+            );
+        }
+
+        #[ink::test]
+        fn airdrop_coupons() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            // airdrop tree of 3 coupons
+            let (coupon_one, coupon_signer) = get_coupon();
+            let leaves = [
+                Ocex::airdrop_leaf(0, &coupon_one, 300),
+                Ocex::airdrop_leaf(1, &accounts.bob, 200),
+                Ocex::airdrop_leaf(2, &accounts.charlie, 100),
+            ];
+            let root = Ocex::merkle_parent(&Ocex::merkle_parent(&leaves[0], &leaves[1]), &leaves[2]);
+            let proof = vec![leaves[1], leaves[2]];
+
+            assert_eq!(contract.add_airdrop(root, 600, None, None), Ok(0));
+            assert_eq!(contract.available_balance(), 400);
+
+            set_sender(accounts.eve);
+            set_balance(accounts.eve, 0);
+
+            let context = signing_context(contract_id().as_ref());
+            let signature = coupon_signer
+                .sign(context.bytes(accounts.eve.as_ref()))
+                .to_bytes();
+            let sign = |deadline: BlockNumber| {
+                let payload = AirdropClaimPayload {
+                    domain: payload_domain(PayloadAction::AirdropClaim, Hash::default()),
+                    airdrop: 0,
+                    index: 0,
+                    coupon: coupon_one,
+                    amount: 300,
+                    receiver: accounts.eve,
+                    deadline,
+                };
+
+                coupon_signer.sign(context.bytes(&payload.encode())).to_bytes()
+            };
+
+            // proof of other amount is rejected
+            assert_eq!(
+                contract.claim_airdrop(
+                    0,
+                    0,
+                    coupon_one,
+                    500,
+                    proof.clone(),
+                    accounts.eve,
+                    None,
+                    signature
+                ),
+                Err(Error::InvalidMerkleProof)
+            );
+
+            // legacy signature is rejected after transition
+            set_sender(accounts.alice);
+            assert_eq!(contract.set_legacy_signatures(false), Ok(true));
+
+            set_sender(accounts.eve);
+            assert_eq!(
+                contract.claim_airdrop(
+                    0,
+                    0,
+                    coupon_one,
+                    300,
+                    proof.clone(),
+                    accounts.eve,
+                    None,
+                    signature
+                ),
+                Err(Error::LegacySignatureDisabled)
+            );
+
+            // versioned claim is valid until the deadline
+            advance_blocks(2);
+            assert_eq!(
+                contract.claim_airdrop(
+                    0,
+                    0,
+                    coupon_one,
+                    300,
+                    proof.clone(),
+                    accounts.eve,
+                    Some(1),
+                    sign(1)
+                ),
+                Err(Error::SignatureExpired)
+            );
+            assert_eq!(
+                contract.claim_airdrop(
+                    0,
+                    0,
+                    coupon_one,
+                    300,
+                    proof.clone(),
+                    accounts.eve,
+                    Some(6),
+                    sign(5)
+                ),
+                Err(Error::VerifySignatureFailed)
+            );
+
+            // scheme of the same key registered as a coupon doesn't apply to airdrop claims
+            set_sender(accounts.alice);
+            assert_eq!(
+                contract.add_coupon_with_options(coupon_one, 100, None, None, KeyScheme::Ed25519),
+                Ok(100)
+            );
+            assert!(contract.burn_coupons(vec![coupon_one]).is_ok());
+
+            set_sender(accounts.eve);
+            assert_eq!(
+                contract.claim_airdrop(
+                    0,
+                    0,
+                    coupon_one,
+                    300,
+                    proof.clone(),
+                    accounts.eve,
+                    Some(5),
+                    sign(5)
+                ),
+                Ok(true)
+            );
+            assert_eq!(get_balance(accounts.eve), 300);
+            assert!(contract.is_airdrop_claimed(0, 0));
+            assert!(!contract.is_airdrop_claimed(0, 1));
+
+            // claim can't be repeated
+            assert_eq!(
+                contract.claim_airdrop(
+                    0,
+                    0,
+                    coupon_one,
+                    300,
+                    proof.clone(),
+                    accounts.eve,
+                    None,
+                    signature
+                ),
+                Err(Error::CouponAlreadyClaimed)
+            );
+
+            // leftover reservation is released on close
+            set_sender(accounts.alice);
+            assert_eq!(contract.close_airdrop(0), Ok(300));
+            assert_eq!(contract.available_balance(), 700);
+        }
+
+        #[ink::test]
+        fn coupon_statuses() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, coupon_signer) = get_coupon();
+            let (coupon_two, _) = get_coupon();
+
+            assert_eq!(
+                contract.coupon_info(coupon_one).status,
+                CouponStatus::NotRegistered
+            );
+
+            set_block_timestamp(5);
+            assert_eq!(contract.add_coupon(coupon_one, 300), Ok(300));
+            assert_eq!(contract.add_coupon(coupon_two, 200), Ok(200));
+
+            let info = contract.coupon_info(coupon_one);
+            assert_eq!(info.status, CouponStatus::Active);
+            assert_eq!((info.issuer, info.issued_at), (Some(accounts.alice), Some(5)));
+
+            // revoked by the owner
+            assert!(contract.burn_coupons(vec![coupon_two]).is_ok());
+            assert_eq!(contract.coupon_info(coupon_two).status, CouponStatus::Revoked);
+
+            // contract is underfunded
+            set_balance(contract_id(), 100);
+            assert_eq!(contract.coupon_info(coupon_one).status, CouponStatus::Underfunded);
+            assert_eq!(contract.check_coupon(coupon_one), (false, 300));
+
+            // redeemed
+            set_balance(contract_id(), 1000);
+            set_block_timestamp(10);
+            set_sender(accounts.eve);
+
+            let context = signing_context(contract_id().as_ref());
+            let signature = coupon_signer.sign(context.bytes(accounts.eve.as_ref()));
+
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
+                Ok(true)
+            );
+
+            let info = contract.coupon_info(coupon_one);
+            assert_eq!(info.status, CouponStatus::Redeemed);
+            assert_eq!((info.receiver, info.redeemed_at), (Some(accounts.eve), Some(10)));
+
+            // redeemed and revoked coupons are rejected with distinct errors
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
+                Err(Error::CouponAlreadyRedeemed)
+            );
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_two, [0; 64]),
+                Err(Error::CouponRevoked)
+            );
+        }
+
+        #[ink::test]
+        fn check_coupon_legacy_output() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, coupon_signer) = get_coupon();
+            let (coupon_two, _) = get_coupon();
+
+            // not registered
+            assert_eq!(contract.check_coupon(coupon_one), (false, 0));
+
+            assert_eq!(contract.add_coupon(coupon_one, 300), Ok(300));
+            assert_eq!(contract.add_coupon(coupon_two, 200), Ok(200));
+            assert_eq!(contract.check_coupon(coupon_one), (true, 300));
+
+            // underfunded
+            set_balance(contract_id(), 100);
+            assert_eq!(contract.check_coupon(coupon_one), (false, 300));
+            set_balance(contract_id(), 1000);
+
+            // revoked coupon keeps the stored amount
+            assert!(contract.burn_coupons(vec![coupon_two]).is_ok());
+            assert_eq!(contract.check_coupon(coupon_two), (false, 200));
+
+            // redeemed coupon keeps the stored amount
+            set_sender(accounts.eve);
+            let context = signing_context(contract_id().as_ref());
+            let signature = coupon_signer.sign(context.bytes(accounts.eve.as_ref()));
+
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
+                Ok(true)
+            );
+            assert_eq!(contract.check_coupon(coupon_one), (false, 300));
+        }
+
+        #[ink::test]
+        fn redemption_receipts() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, coupon_signer) = get_coupon();
+            assert_eq!(contract.add_coupon(coupon_one, 500), Ok(500));
+            assert_eq!(contract.coupon_receipt(coupon_one), None);
+
+            set_sender(accounts.eve);
+            let context = signing_context(contract_id().as_ref());
+            let sign = |receiver: AccountId, amount: Balance, nonce: u32| {
+                let payload = RedemptionPayload {
+                    domain: payload_domain(PayloadAction::Redemption, Hash::default()),
+                    coupon: coupon_one,
+                    receiver,
+                    amount,
+                    nonce,
+                    deadline: 10,
+                };
+
+                coupon_signer.sign(context.bytes(&payload.encode())).to_bytes()
+            };
+
+            // two partial redemptions to different receivers in different blocks
+            assert_eq!(
+                contract.redeem_coupon(accounts.eve, coupon_one, 200, 10, sign(accounts.eve, 200, 0)),
+                Ok(200)
+            );
+
+            advance_blocks(3);
+            set_block_timestamp(20);
+            assert_eq!(
+                contract.redeem_coupon(accounts.frank, coupon_one, 100, 10, sign(accounts.frank, 100, 1)),
+                Ok(100)
+            );
+
+            // receipt keeps the last payment apart from the total
+            assert_eq!(
+                contract.coupon_receipt(coupon_one),
+                Some(Receipt {
+                    receiver: accounts.frank,
+                    amount: 100,
+                    fee: 0,
+                    total: 300,
+                    block: 3,
+                    timestamp: 20,
+                })
+            );
+            assert_eq!(contract.receiver_redemptions(accounts.eve), 1);
+            assert_eq!(contract.receiver_redemptions(accounts.frank), 1);
+            assert_eq!(contract.receiver_redemptions(accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn list_coupons_pages() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, coupon_signer) = get_coupon();
+            let (coupon_two, _) = get_coupon();
+            let (coupon_three, _) = get_coupon();
+            let coupons = vec![coupon_one, coupon_two, coupon_three];
+
+            assert!(contract
+                .add_coupons(coupons.clone(), 100, None, None, KeyScheme::Sr25519)
+                .is_ok());
+
+            // pages in the insertion order
+            assert_eq!(
+                contract.list_coupons(0, 2, None),
+                Ok((vec![coupon_one, coupon_two], Some(2)))
+            );
+            assert_eq!(contract.list_coupons(2, 2, None), Ok((vec![coupon_three], None)));
+            assert_eq!(
+                contract.list_coupons(0, 10, Some(CouponStatus::Active)),
+                Ok((coupons.clone(), None))
+            );
+
+            // redeemed and revoked coupons move to their lists
+            set_sender(accounts.eve);
+            let context = signing_context(contract_id().as_ref());
+            let signature = coupon_signer.sign(context.bytes(accounts.eve.as_ref()));
+
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
+                Ok(true)
+            );
+
+            set_sender(accounts.alice);
+            assert!(contract.burn_coupons(vec![coupon_two]).is_ok());
+
+            assert_eq!(
+                contract.list_coupons(0, 10, Some(CouponStatus::Active)),
+                Ok((vec![coupon_three], None))
+            );
+            assert_eq!(
+                contract.list_coupons(0, 10, Some(CouponStatus::Redeemed)),
+                Ok((vec![coupon_one], None))
+            );
+            assert_eq!(
+                contract.list_coupons(0, 10, Some(CouponStatus::Revoked)),
+                Ok((vec![coupon_two], None))
+            );
+            assert_eq!(
+                contract.list_coupons(0, 10, Some(CouponStatus::Underfunded)),
+                Err(Error::StatusNotIndexed)
+            );
+            assert_eq!(
+                contract.list_coupons(0, 10, Some(CouponStatus::Expired)),
+                Err(Error::StatusNotIndexed)
+            );
+            assert_eq!(contract.list_coupons(0, 10, None), Ok((coupons, None)));
+
+            assert_eq!(
+                contract.coupon_counts(),
+                CouponCounts {
+                    total: 3,
+                    active: 1,
+                    redeemed: 1,
+                    revoked: 1,
+                    reclaimed: 0,
+                    rekeyed: 0,
+                    cancelled: 0,
+                    pruned: 0,
+                }
+            );
+        }
+
+        #[ink::test]
+        fn prune_finished_coupons() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, coupon_signer) = get_coupon();
+            let (coupon_two, _) = get_coupon();
+            let (coupon_three, _) = get_coupon();
+            let coupons = vec![coupon_one, coupon_two, coupon_three];
+
+            assert_eq!(contract.add_coupon(coupon_one, 100), Ok(100));
+            assert_eq!(
+                contract.add_coupon_with_options(
+                    coupon_two,
+                    100,
+                    Some(Expiry::BlockNumber(10)),
+                    None,
+                    KeyScheme::Sr25519
+                ),
+                Ok(100)
+            );
+            assert_eq!(contract.add_coupon(coupon_three, 100), Ok(100));
+
+            set_sender(accounts.eve);
+            let context = signing_context(contract_id().as_ref());
+            let signature = coupon_signer.sign(context.bytes(accounts.eve.as_ref()));
+
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
+                Ok(true)
+            );
+            assert_eq!(
+                contract.prune_coupons(coupons.clone()),
+                Err(Error::MissingRole(Role::Revoker))
+            );
+
+            // active coupon is skipped, finished coupons free their entries and status positions
+            set_sender(accounts.alice);
+            assert!(contract.burn_coupons(vec![coupon_two]).is_ok());
+            assert_eq!(contract.prune_coupons(coupons.clone()), Ok(9));
+            assert_eq!(contract.prune_coupons(coupons.clone()), Ok(0));
+
+            // pruned coupon can't be added & redeemed again
+            assert_eq!(
+                contract.add_coupon(coupon_one, 100),
+                Err(Error::CouponAlreadyExists)
+            );
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
+                Err(Error::CouponAlreadyRedeemed)
+            );
+            assert_eq!(contract.coupon_info(coupon_one).status, CouponStatus::Redeemed);
+            assert_eq!(contract.coupon_info(coupon_two).status, CouponStatus::Revoked);
+            assert_eq!(contract.available_balance(), 800);
+
+            assert_eq!(contract.list_coupons(0, 10, None), Ok((coupons, None)));
+            assert_eq!(
+                contract.coupon_counts(),
+                CouponCounts {
+                    total: 3,
+                    active: 1,
+                    redeemed: 0,
+                    revoked: 0,
+                    reclaimed: 0,
+                    rekeyed: 0,
+                    cancelled: 0,
+                    pruned: 2,
+                }
+            );
+            assert!(contract.coupon_receipt(coupon_one).is_some());
+
+            // vesting positions are freed once they are fully claimed
+            let (coupon_four, coupon_four_signer) = get_coupon();
+            let (coupon_five, coupon_five_signer) = get_coupon();
+            let vestings = vec![coupon_four, coupon_five];
+
+            for coupon in vestings.iter() {
+                assert_eq!(
+                    contract.add_vesting_coupon(
+                        *coupon,
+                        100,
+                        None,
+                        None,
+                        0,
+                        1000,
+                        true,
+                        KeyScheme::Sr25519,
+                        None
+                    ),
+                    Ok(100)
+                );
+            }
+
+            set_block_timestamp(0);
+            set_sender(accounts.eve);
+            for (coupon, signer) in [(coupon_four, &coupon_four_signer), (coupon_five, &coupon_five_signer)] {
+                let signature = signer.sign(context.bytes(accounts.eve.as_ref()));
+                assert_eq!(
+                    contract.activate_coupon(accounts.eve, coupon, signature.to_bytes()),
+                    Ok(true)
+                );
+            }
+
+            set_block_timestamp(500);
+            assert_eq!(contract.claim_vested(coupon_four), Ok(50));
+
+            set_sender(accounts.alice);
+            assert_eq!(contract.cancel_vesting(coupon_five), Ok(50));
+            assert_eq!(contract.prune_coupons(vestings.clone()), Ok(10));
+            assert!(contract.vesting(coupon_four).is_some());
+            assert!(contract.vesting(coupon_five).is_some());
+
+            // vested part of the cancelled position is claimed
+            set_block_timestamp(1000);
+            set_sender(accounts.eve);
+            assert_eq!(contract.claim_vested(coupon_four), Ok(50));
+            assert_eq!(contract.claim_vested(coupon_five), Ok(50));
+
+            set_sender(accounts.alice);
+            assert_eq!(contract.prune_coupons(vestings), Ok(2));
+            assert_eq!(contract.vesting(coupon_four), None);
+            assert_eq!(contract.vesting(coupon_five), None);
+            assert!(contract.coupon_receipt(coupon_four).is_some());
+            assert_eq!(contract.available_balance(), 650);
+        }
+
+        #[ink::test]
+        fn relayed_activation() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, coupon_signer) = get_coupon();
+            assert_eq!(contract.add_coupon(coupon_one, 300), Ok(300));
+
+            let (coupon_two, coupon_two_signer) = get_coupon();
+            assert_eq!(contract.add_coupon(coupon_two, 100), Ok(100));
+
+            let context = signing_context(contract_id().as_ref());
+            let sign_coupon = |coupon: &CouponId,
+                               signer: &Keypair,
+                               fee: Balance,
+                               relayer: Option<AccountId>,
+                               deadline: BlockNumber| {
+                let payload = RelayPayload {
+                    domain: payload_domain(PayloadAction::Relay, Hash::default()),
+                    coupon: *coupon,
+                    receiver: accounts.eve,
+                    fee,
+                    relayer,
+                    deadline,
+                };
+
+                signer.sign(context.bytes(&payload.encode())).to_bytes()
+            };
+            let sign = |fee: Balance, relayer: Option<AccountId>, deadline: BlockNumber| {
+                sign_coupon(&coupon_one, &coupon_signer, fee, relayer, deadline)
+            };
+
+            // relayer can't take a fee until the limits are set
+            set_sender(accounts.charlie);
+            assert_eq!(
+                contract.relay_activate_coupon(accounts.eve, coupon_one, 30, None, 5, sign(30, None, 5)),
+                Err(Error::RelayerFeeTooHigh)
+            );
+            assert_eq!(
+                contract.set_relayer_fee_limits(50, 1000),
+                Err(Error::MissingRole(Role::Admin))
+            );
+
+            set_sender(accounts.alice);
+            assert_eq!(
+                contract.set_relayer_fee_limits(50, 10_001),
+                Err(Error::InvalidFeeLimits)
+            );
+            // fee can't exceed the coupon amount even without limits
+            let huge_fee = Balance::MAX / 2;
+            assert_eq!(contract.set_relayer_fee_limits(Balance::MAX, 10_000), Ok(true));
+
+            set_sender(accounts.charlie);
+            assert_eq!(
+                contract.relay_activate_coupon(
+                    accounts.eve,
+                    coupon_one,
+                    huge_fee,
+                    None,
+                    5,
+                    sign(huge_fee, None, 5)
+                ),
+                Err(Error::RelayerFeeTooHigh)
+            );
+
+            set_sender(accounts.alice);
+            assert_eq!(contract.set_relayer_fee_limits(50, 1000), Ok(true));
+
+            // fee is limited by 10% of the coupon amount
+            set_sender(accounts.charlie);
+            assert_eq!(
+                contract.relay_activate_coupon(accounts.eve, coupon_one, 40, None, 5, sign(40, None, 5)),
+                Err(Error::RelayerFeeTooHigh)
+            );
+
+            // only the signed relayer can relay the activation for the signed fee
+            let relayer = Some(accounts.charlie);
+            let signature = sign(30, relayer, 5);
+
+            set_sender(accounts.django);
+            assert_eq!(
+                contract.relay_activate_coupon(accounts.eve, coupon_one, 30, relayer, 5, signature),
+                Err(Error::InvalidRelayer)
+            );
+
+            set_sender(accounts.charlie);
+            assert_eq!(
+                contract.relay_activate_coupon(accounts.eve, coupon_one, 20, relayer, 5, signature),
+                Err(Error::VerifySignatureFailed)
+            );
+
+            // deadline is signed and expired signatures are rejected
+            assert_eq!(
+                contract.relay_activate_coupon(accounts.eve, coupon_one, 30, relayer, 6, signature),
+                Err(Error::VerifySignatureFailed)
+            );
+
+            advance_blocks(2);
+            assert_eq!(
+                contract.relay_activate_coupon(
+                    accounts.eve,
+                    coupon_one,
+                    30,
+                    relayer,
+                    1,
+                    sign(30, relayer, 1)
+                ),
+                Err(Error::SignatureExpired)
+            );
+
+            set_balance(accounts.eve, 0);
+            set_balance(accounts.charlie, 0);
+            assert_eq!(
+                contract.relay_activate_coupon(accounts.eve, coupon_one, 30, relayer, 5, signature),
+                Ok(270)
+            );
+            assert_eq!(
+                (get_balance(accounts.eve), get_balance(accounts.charlie)),
+                (270, 30)
+            );
+            assert_eq!(contract.coupon_info(coupon_one).status, CouponStatus::Redeemed);
+
+            // receiver is credited with the net amount, the fee is kept apart
+            let receipt = contract.coupon_receipt(coupon_one).unwrap();
+            assert_eq!(
+                (receipt.receiver, receipt.amount, receipt.fee, receipt.total),
+                (accounts.eve, 270, 30, 300)
+            );
+
+            // relayer fee is reported only when it's paid
+            assert_eq!(
+                contract.relay_activate_coupon(
+                    accounts.eve,
+                    coupon_two,
+                    0,
+                    None,
+                    5,
+                    sign_coupon(&coupon_two, &coupon_two_signer, 0, None, 5)
+                ),
+                Ok(100)
+            );
+            assert_eq!(get_balance(accounts.charlie), 30);
+
+            let raw_events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            let fees = decode_events(&raw_events)
+                .into_iter()
+                .filter_map(|event| match event {
+                    Event::RelayerFeePaid(RelayerFeePaid { coupon, relayer, fee }) => {
+                        Some((coupon, relayer, fee))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(fees, vec![(coupon_one, accounts.charlie, 30)]);
+        }
+
+        #[ink::test]
+        fn coupon_key_schemes() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let ed25519_pair = ed25519::Pair::from_seed(&[1; 32]);
+            let ed25519_coupon =
+                CouponId::from(<[u8; 32]>::try_from(ed25519_pair.public().as_ref()).unwrap());
+
+            // ECDSA coupon is the Ethereum address of the key
+            let ecdsa_pair = ecdsa::Pair::from_seed(&[2; 32]);
+            let mut ecdsa_coupon = [0; 32];
+            ink::env::ecdsa_to_eth_address(
+                &ecdsa_pair.public().as_ref().try_into().unwrap(),
+                (&mut ecdsa_coupon[..20]).try_into().unwrap(),
+            )
+            .unwrap();
+            let ecdsa_coupon = CouponId::from(ecdsa_coupon);
+
+            assert_eq!(
+                contract.add_coupon_with_options(ed25519_coupon, 100, None, None, KeyScheme::Ed25519),
+                Ok(100)
+            );
+            assert_eq!(
+                contract.add_coupon_with_options(ecdsa_coupon, 200, None, None, KeyScheme::Ecdsa),
+                Ok(200)
+            );
+            assert_eq!(contract.coupon_scheme(ed25519_coupon), KeyScheme::Ed25519);
+            assert_eq!(contract.coupon_scheme(accounts.bob), KeyScheme::Sr25519);
+
+            // key scheme is set by every registration message
+            let batch_pair = ed25519::Pair::from_seed(&[4; 32]);
+            let batch_coupon = CouponId::from(<[u8; 32]>::try_from(batch_pair.public().as_ref()).unwrap());
+            assert_eq!(
+                contract
+                    .add_coupons_with_amounts(vec![(batch_coupon, 50)], None, None, KeyScheme::Ed25519)
+                    .map(|result| result.accepted),
+                Ok(vec![batch_coupon])
+            );
+            assert_eq!(contract.coupon_scheme(batch_coupon), KeyScheme::Ed25519);
+
+            set_sender(accounts.eve);
+            set_balance(accounts.eve, 0);
+            let message = (contract_id(), accounts.eve).encode();
+
+            // signature of another key is rejected
+            let signature = signature_bytes(eth_sign(&ecdsa::Pair::from_seed(&[3; 32]), &message).as_ref());
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, ecdsa_coupon, signature),
+                Err(Error::VerifySignatureFailed)
+            );
+
+            let signature = signature_bytes(ed25519_pair.sign(&message).as_ref());
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, ed25519_coupon, signature),
+                Ok(true)
+            );
+
+            // recovery id isn't part of the signature
+            let signature = signature_bytes(eth_sign(&ecdsa_pair, &message).as_ref());
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, ecdsa_coupon, signature),
+                Ok(true)
+            );
+
+            let signature = signature_bytes(batch_pair.sign(&message).as_ref());
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, batch_coupon, signature),
+                Ok(true)
+            );
+            assert_eq!(get_balance(accounts.eve), 350);
+        }
+
+        #[ink::test]
+        fn versioned_activation_payload() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, coupon_signer) = get_coupon();
+            let (coupon_two, _) = get_coupon();
+            assert_eq!(contract.add_coupon(coupon_one, 300), Ok(300));
+            assert_eq!(contract.add_coupon(coupon_two, 200), Ok(200));
+            assert_eq!(contract.set_chain_id(Hash::from([7; 32])), Ok(true));
+
+            let context = signing_context(contract_id().as_ref());
+            let sign_action = |action: PayloadAction, deadline: BlockNumber, chain: Hash| {
+                let payload = ActivationPayload {
+                    domain: payload_domain(action, chain),
+                    coupon: coupon_one,
+                    receiver: accounts.eve,
+                    deadline,
+                };
+
+                coupon_signer.sign(context.bytes(&payload.encode())).to_bytes()
+            };
+            let sign =
+                |deadline: BlockNumber, chain: Hash| sign_action(PayloadAction::Activation, deadline, chain);
+
+            set_sender(accounts.eve);
+            assert_eq!(
+                contract.set_chain_id(Hash::from([8; 32])),
+                Err(Error::MissingRole(Role::Admin))
+            );
+
+            // action is signed, signature of another action is rejected
+            assert_eq!(
+                contract.activate_coupon_with_deadline(
+                    accounts.eve,
+                    coupon_one,
+                    5,
+                    sign_action(PayloadAction::Redemption, 5, Hash::from([7; 32]))
+                ),
+                Err(Error::VerifySignatureFailed)
+            );
+
+            // deadline and chain are signed
+            assert_eq!(
+                contract.activate_coupon_with_deadline(
+                    accounts.eve,
+                    coupon_one,
+                    6,
+                    sign(5, Hash::from([7; 32]))
+                ),
+                Err(Error::VerifySignatureFailed)
+            );
+            assert_eq!(
+                contract.activate_coupon_with_deadline(
+                    accounts.eve,
+                    coupon_one,
+                    5,
+                    sign(5, Hash::from([8; 32]))
+                ),
+                Err(Error::VerifySignatureFailed)
+            );
+
+            advance_blocks(6);
+            assert_eq!(
+                contract.activate_coupon_with_deadline(
+                    accounts.eve,
+                    coupon_one,
+                    5,
+                    sign(5, Hash::from([7; 32]))
+                ),
+                Err(Error::SignatureExpired)
+            );
+            assert_eq!(
+                contract.activate_coupon_with_deadline(
+                    accounts.eve,
+                    coupon_one,
+                    6,
+                    sign(6, Hash::from([7; 32]))
+                ),
+                Ok(true)
+            );
+
+            // legacy signatures can be disabled after transition
+            set_sender(accounts.eve);
+            assert_eq!(
+                contract.set_legacy_signatures(false),
+                Err(Error::MissingRole(Role::Admin))
+            );
+
+            set_sender(accounts.alice);
+            assert!(contract.legacy_signatures());
+            assert_eq!(contract.set_legacy_signatures(false), Ok(true));
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_two, [0; 64]),
+                Err(Error::LegacySignatureDisabled)
+            );
+        }
+
+        #[ink::test]
+        fn receiver_bound_coupons() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, coupon_signer) = get_coupon();
+            let receivers = vec![accounts.eve, accounts.frank];
+
+            assert_eq!(
+                contract.add_bound_coupon(coupon_one, 300, None, None, vec![], KeyScheme::Sr25519),
+                Err(Error::InvalidReceiversCount)
+            );
+            assert_eq!(
+                contract.add_bound_coupon(coupon_one, 300, None, None, receivers.clone(), KeyScheme::Sr25519),
+                Ok(300)
+            );
+            assert_eq!(contract.coupon_info(coupon_one).receivers, receivers);
+
+            // coupon pays only to the bound receivers
+            let context = signing_context(contract_id().as_ref());
+            let sign = |receiver: AccountId| coupon_signer.sign(context.bytes(receiver.as_ref())).to_bytes();
+
+            set_sender(accounts.django);
+            assert_eq!(
+                contract.activate_coupon(accounts.django, coupon_one, sign(accounts.django)),
+                Err(Error::ReceiverNotAllowed)
+            );
+
+            set_sender(accounts.frank);
+            assert_eq!(
+                contract.activate_coupon(accounts.frank, coupon_one, sign(accounts.frank)),
+                Ok(true)
+            );
+        }
+
+        #[ink::test]
+        fn multi_use_coupons() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, coupon_signer) = get_coupon();
+
+            assert_eq!(
+                contract.add_multi_use_coupon(coupon_one, Balance::MAX, 2, None, None, KeyScheme::Sr25519),
+                Err(Error::ArithmeticOverflow)
+            );
+            assert_eq!(
+                contract.add_multi_use_coupon(coupon_one, 0, 3, None, None, KeyScheme::Sr25519),
+                Err(Error::InvalidMultiUseTerms)
+            );
+            assert_eq!(
+                contract.add_multi_use_coupon(coupon_one, 100, 0, None, None, KeyScheme::Sr25519),
+                Err(Error::InvalidMultiUseTerms)
+            );
+            assert_eq!(
+                contract.add_multi_use_coupon(coupon_one, 100, 3, None, None, KeyScheme::Sr25519),
+                Ok(300)
+            );
+            assert_eq!(contract.available_balance(), 700);
+
+            let context = signing_context(contract_id().as_ref());
+            let sign = |receiver: AccountId| coupon_signer.sign(context.bytes(receiver.as_ref())).to_bytes();
+
+            // every receiver is paid once
+            set_sender(accounts.eve);
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, sign(accounts.eve)),
+                Ok(true)
+            );
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, sign(accounts.eve)),
+                Err(Error::CouponAlreadyClaimed)
+            );
+
+            set_sender(accounts.frank);
+            assert_eq!(
+                contract.activate_coupon(accounts.frank, coupon_one, sign(accounts.frank)),
+                Ok(true)
+            );
+            assert_eq!(
+                contract.redeem_coupon(accounts.frank, coupon_one, 100, 0, [0; 64]),
+                Err(Error::MultiUseCoupon)
+            );
+
+            let info = contract.coupon_info(coupon_one);
+            assert_eq!((info.status, info.amount), (CouponStatus::Active, 100));
+            assert_eq!(
+                contract.coupon_uses(coupon_one),
+                Some(MultiUse {
+                    amount: 100,
+                    max_redemptions: 3,
+                    redemptions: 2,
+                })
+            );
+
+            // unused remainder is released on revocation
+            set_sender(accounts.alice);
+            assert!(contract.burn_coupons(vec![coupon_one]).is_ok());
+            assert_eq!(contract.available_balance(), 800);
+        }
+
+        #[ink::test]
+        fn vesting_coupons() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, coupon_signer) = get_coupon();
+
+            // cliff can't be longer than the duration
+            assert_eq!(
+                contract.add_vesting_coupon(
+                    coupon_one,
+                    400,
+                    None,
+                    None,
+                    5000,
+                    4000,
+                    true,
+                    KeyScheme::Sr25519,
+                    None
+                ),
+                Err(Error::InvalidVestingTerms)
+            );
+            assert_eq!(contract.available_balance(), 1000);
+
+            assert_eq!(
+                contract.add_vesting_coupon(
+                    coupon_one,
+                    400,
+                    None,
+                    None,
+                    1000,
+                    4000,
+                    true,
+                    KeyScheme::Sr25519,
+                    None
+                ),
+                Ok(400)
+            );
+            assert_eq!(contract.available_balance(), 600);
+            assert_eq!(
+                contract.redeem_coupon(accounts.eve, coupon_one, 100, 0, [0; 64]),
+                Err(Error::VestingCoupon)
+            );
+
+            // activation starts the vesting position, reservation is kept
+            set_block_timestamp(0);
+            set_sender(accounts.eve);
+            let context = signing_context(contract_id().as_ref());
+            let signature = coupon_signer
+                .sign(context.bytes(accounts.eve.as_ref()))
+                .to_bytes();
+
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature),
+                Ok(true)
+            );
+            assert!(!contract.check_coupon(coupon_one).0);
+
+            // nothing is vested before the cliff
+            set_block_timestamp(500);
+            assert_eq!(contract.claim_vested(coupon_one), Err(Error::NothingToClaim));
+
+            set_block_timestamp(2000);
+            set_sender(accounts.frank);
+            assert_eq!(contract.claim_vested(coupon_one), Err(Error::NotVestingReceiver));
+
+            set_sender(accounts.eve);
+            assert_eq!(contract.claim_vested(coupon_one), Ok(200));
+            assert_eq!(contract.claim_vested(coupon_one), Err(Error::NothingToClaim));
+
+            // revoker cancels the unvested part
+            set_block_timestamp(3000);
+            assert_eq!(
+                contract.cancel_vesting(coupon_one),
+                Err(Error::MissingRole(Role::Revoker))
+            );
+
+            set_sender(accounts.alice);
+            assert_eq!(contract.cancel_vesting(coupon_one), Ok(100));
+            assert_eq!(
+                contract.cancel_vesting(coupon_one),
+                Err(Error::VestingNotRevocable)
+            );
+            assert_eq!(contract.available_balance(), 700);
+
+            // vested part is still claimable
+            set_block_timestamp(5000);
+            set_sender(accounts.eve);
+            assert_eq!(contract.claim_vested(coupon_one), Ok(100));
+            assert_eq!(
+                contract.vesting(coupon_one).map(|vesting| vesting.claimed),
+                Some(300)
+            );
+
+            set_sender(accounts.alice);
+            assert_eq!(contract.available_balance(), 700);
+
+            // campaign totals follow claims and cancellation of the vesting position
+            let (coupon_two, coupon_two_signer) = get_coupon();
+            assert_eq!(
+                contract.create_campaign(String::from("Grants"), 200, None, None, None),
+                Ok(0)
+            );
+            assert_eq!(
+                contract.add_vesting_coupon(
+                    coupon_two,
+                    200,
+                    None,
+                    Some(AccountId::from([0x77; 32])),
+                    0,
+                    1000,
+                    true,
+                    KeyScheme::Sr25519,
+                    Some(0)
+                ),
+                Err(Error::CampaignTokenMismatch)
+            );
+            assert_eq!(
+                contract.add_vesting_coupon(
+                    coupon_two,
+                    300,
+                    None,
+                    None,
+                    0,
+                    1000,
+                    true,
+                    KeyScheme::Sr25519,
+                    Some(0)
+                ),
+                Err(Error::CampaignBudgetExceeded)
+            );
+            assert_eq!(
+                contract.add_vesting_coupon(
+                    coupon_two,
+                    200,
+                    None,
+                    None,
+                    0,
+                    1000,
+                    true,
+                    KeyScheme::Sr25519,
+                    Some(0)
+                ),
+                Ok(200)
+            );
+            assert_eq!(contract.coupon_campaign(coupon_two), Some(0));
+
+            let totals = |contract: &Ocex| {
+                let campaign = contract.campaign(0).unwrap();
+                (campaign.reserved, campaign.redeemed, campaign.burned)
+            };
+
+            set_sender(accounts.eve);
+            let signature = coupon_two_signer
+                .sign(context.bytes(accounts.eve.as_ref()))
+                .to_bytes();
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_two, signature),
+                Ok(true)
+            );
+            assert_eq!(totals(&contract), (200, 0, 0));
+
+            set_block_timestamp(5500);
+            assert_eq!(contract.claim_vested(coupon_two), Ok(100));
+            assert_eq!(totals(&contract), (100, 100, 0));
+
+            set_sender(accounts.alice);
+            assert_eq!(contract.cancel_vesting(coupon_two), Ok(100));
+            assert_eq!(totals(&contract), (0, 100, 100));
+            assert_eq!(contract.available_balance(), 600);
+        }
+
+        #[ink::test]
+        fn rekey_coupons() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, coupon_one_signer) = get_coupon();
+            let (coupon_two, coupon_two_signer) = get_coupon();
+            let (coupon_three, _) = get_coupon();
+
+            assert_eq!(
+                contract.create_campaign(String::from("Winter"), 500, None, None, None),
+                Ok(0)
+            );
+            assert!(contract
+                .add_campaign_coupons(0, vec![(coupon_one, 300)], None, KeyScheme::Sr25519)
+                .is_ok());
+            assert_eq!(contract.add_coupon(coupon_three, 100), Ok(100));
+
+            let context = signing_context(contract_id().as_ref());
+            let sign = |signer: &Keypair, coupon: CouponId, new_coupon: CouponId| {
+                let payload = RekeyPayload {
+                    domain: payload_domain(PayloadAction::Rekey, Hash::default()),
+                    coupon,
+                    new_coupon,
+                };
+
+                signer.sign(context.bytes(&payload.encode())).to_bytes()
+            };
+
+            // new key is signed by the old key
+            set_sender(accounts.eve);
+            assert_eq!(
+                contract.rekey_coupon(
+                    coupon_one,
+                    coupon_two,
+                    sign(&coupon_two_signer, coupon_one, coupon_two)
+                ),
+                Err(Error::VerifySignatureFailed)
+            );
+            assert_eq!(
+                contract.rekey_coupon(
+                    coupon_one,
+                    coupon_three,
+                    sign(&coupon_one_signer, coupon_one, coupon_three)
+                ),
+                Err(Error::CouponAlreadyExists)
+            );
+            assert_eq!(
+                contract.rekey_coupon(
+                    coupon_one,
+                    coupon_two,
+                    sign(&coupon_one_signer, coupon_one, coupon_two)
+                ),
+                Ok(300)
+            );
+
+            // old key is nullified
+            let signature = coupon_one_signer
+                .sign(context.bytes(accounts.eve.as_ref()))
+                .to_bytes();
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature),
+                Err(Error::CouponRekeyed)
+            );
+            assert_eq!(contract.coupon_info(coupon_one).status, CouponStatus::Rekeyed);
+
+            let info = contract.coupon_info(coupon_two);
+            assert_eq!(
+                (info.status, info.amount, info.campaign, info.issuer),
+                (CouponStatus::Active, 300, Some(0), Some(accounts.alice))
+            );
+            assert_eq!(
+                contract.rekey_coupon(
+                    coupon_two,
+                    coupon_one,
+                    sign(&coupon_two_signer, coupon_two, coupon_one)
+                ),
+                Err(Error::CouponAlreadyExists)
+            );
+
+            // new key is activated by its signature
+            let signature = coupon_two_signer
+                .sign(context.bytes(accounts.eve.as_ref()))
+                .to_bytes();
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_two, signature),
+                Ok(true)
+            );
+
+            set_sender(accounts.alice);
+            assert_eq!(contract.available_balance(), 600);
+            assert_eq!(contract.campaign(0).map(|campaign| campaign.redeemed), Some(300));
+            assert_eq!(contract.coupon_counts().rekeyed, 1);
+        }
+
+        #[ink::test]
+        fn cancel_coupons() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, coupon_signer) = get_coupon();
+            let (coupon_two, _) = get_coupon();
+
+            assert_eq!(contract.add_coupon(coupon_one, 300), Ok(300));
+            assert_eq!(contract.add_coupon(coupon_two, 200), Ok(200));
+
+            let context = signing_context(contract_id().as_ref());
+            let sign = |coupon: CouponId| {
+                let payload = CancellationPayload {
+                    domain: payload_domain(PayloadAction::Cancellation, Hash::default()),
+                    coupon,
+                };
+
+                coupon_signer.sign(context.bytes(&payload.encode())).to_bytes()
+            };
+
+            // cancellation is stopped by the redemption pause
+            assert_eq!(contract.set_paused(false, true), Ok(true));
+            assert_eq!(
+                contract.cancel_coupon(coupon_one, sign(coupon_one)),
+                Err(Error::Paused)
+            );
+            assert_eq!(contract.set_paused(false, false), Ok(true));
+
+            // cancellation is signed by the coupon key
+            set_sender(accounts.eve);
+            assert_eq!(
+                contract.cancel_coupon(coupon_two, sign(coupon_two)),
+                Err(Error::VerifySignatureFailed)
+            );
+            assert_eq!(contract.cancel_coupon(coupon_one, sign(coupon_one)), Ok(300));
+            assert_eq!(
+                contract.cancel_coupon(coupon_one, sign(coupon_one)),
+                Err(Error::CouponCancelled)
+            );
+
+            let signature = coupon_signer
+                .sign(context.bytes(accounts.eve.as_ref()))
+                .to_bytes();
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature),
+                Err(Error::CouponCancelled)
+            );
+
+            // cancellation is reported apart from revocation
+            set_sender(accounts.alice);
+            assert!(contract.burn_coupons(vec![coupon_two]).is_ok());
+            assert_eq!(contract.coupon_info(coupon_one).status, CouponStatus::Cancelled);
+            assert_eq!(contract.coupon_info(coupon_two).status, CouponStatus::Revoked);
+            assert_eq!(
+                contract.list_coupons(0, 10, Some(CouponStatus::Cancelled)),
+                Ok((vec![coupon_one], None))
+            );
+
+            let counts = contract.coupon_counts();
+            assert_eq!((counts.cancelled, counts.revoked), (1, 1));
+            assert_eq!(contract.available_balance(), 1000);
+        }
+
+        #[ink::test]
+        fn token_coupons() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let token = AccountId::from([0x77; 32]);
+            psp22::mint(token, contract_id(), 500);
+
+            let (coupon_one, coupon_signer) = get_coupon();
+            let (coupon_two, _) = get_coupon();
+
+            // token coupons are reserved from the token balance only
+            assert_eq!(
+                contract.add_coupon_with_options(coupon_one, 600, None, Some(token), KeyScheme::Sr25519),
+                Err(Error::ContractBalanceNotEnough)
+            );
+            assert_eq!(
+                contract.add_coupon_with_options(coupon_one, 300, None, Some(token), KeyScheme::Sr25519),
+                Ok(300)
+            );
+            assert_eq!(
+                contract.add_coupon_with_options(coupon_two, 300, None, Some(token), KeyScheme::Sr25519),
+                Err(Error::ContractBalanceNotEnough)
+            );
+            assert_eq!(contract.add_coupon(coupon_two, 800), Ok(800));
+            assert_eq!(contract.available_token_balance(token), 200);
+            assert_eq!(contract.available_balance(), 200);
+            assert_eq!(contract.coupon_token(coupon_one), Some(token));
+
+            // token coupon pays in tokens
+            let eve_balance = get_balance(accounts.eve);
+            set_sender(accounts.eve);
+            let context = signing_context(contract_id().as_ref());
+            let signature = coupon_signer
+                .sign(context.bytes(accounts.eve.as_ref()))
+                .to_bytes();
+
+            // failed PSP22 transfer leaves the coupon unredeemed
+            psp22::freeze(token, true);
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature),
+                Err(Error::TransferFailed)
+            );
+            assert_eq!(contract.check_coupon(coupon_one), (true, 300));
+            assert_eq!(contract.coupon_info(coupon_one).status, CouponStatus::Active);
+            assert_eq!(psp22::balance_of(token, accounts.eve), 0);
+
+            psp22::freeze(token, false);
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature),
+                Ok(true)
+            );
+            assert_eq!(psp22::balance_of(token, accounts.eve), 300);
+            assert_eq!(get_balance(accounts.eve), eve_balance);
+
+            // only spare tokens are paid back to the owner
+            set_sender(accounts.alice);
+            assert_eq!(contract.transfer_ownership(accounts.bob), Ok(true));
+
+            set_sender(accounts.bob);
+            assert_eq!(contract.available_token_balance(token), 200);
+            assert_eq!(contract.payback_not_reserved_tokens(token), Ok(true));
+            assert_eq!(psp22::balance_of(token, accounts.bob), 200);
+            assert_eq!(psp22::balance_of(token, contract_id()), 0);
+            assert_eq!(contract.available_balance(), 200);
+        }
+
+        #[ink::test]
+        fn psp22_transfer_results() {
+            // PSP22 error and failed call are failed transfers
+            assert_eq!(Ocex::transfer_result(Ok(Ok(Ok(())))), Ok(()));
+            assert_eq!(
+                Ocex::transfer_result(Ok(Ok(Err(PSP22Error::InsufficientBalance)))),
+                Err(Error::TransferFailed)
+            );
+            assert_eq!(
+                Ocex::transfer_result(Ok(Err(ink::LangError::CouldNotReadInput))),
+                Err(Error::TransferFailed)
+            );
+            assert_eq!(
+                Ocex::transfer_result(Err(ink::env::Error::CalleeTrapped)),
+                Err(Error::TransferFailed)
+            );
+        }
+
+        #[ink::test]
+        fn psp22_calls() {
+            let accounts = default_accounts();
+            let token = AccountId::from([0x77; 32]);
+
+            // calls of the token contract encode the PSP22 selectors with SCALE encoded arguments
+            let transfer = Psp22Calls::transfer_params(token, accounts.eve, 100);
+            assert_eq!(transfer.callee(), &token);
+            assert_eq!(
+                transfer.exec_input().encode(),
+                [
+                    &ink::selector_bytes!("PSP22::transfer")[..],
+                    &(accounts.eve, 100 as Balance, Vec::<u8>::new()).encode(),
+                ]
+                .concat()
+            );
+
+            let balance_of = Psp22Calls::balance_of_params(token, contract_id());
+            assert_eq!(balance_of.callee(), &token);
+            assert_eq!(
+                balance_of.exec_input().encode(),
+                [&ink::selector_bytes!("PSP22::balance_of")[..], &contract_id().encode()].concat()
+            );
+        }
+
+        #[ink::test]
+        fn double_burn_coupons() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            let (coupon_one, coupon_signer) = get_coupon();
+            let (coupon_two, _) = get_coupon();
+            let (coupon_three, _) = get_coupon();
+
+            assert_eq!(contract.add_coupon(coupon_one, 300), Ok(300));
+            assert_eq!(contract.add_coupon(coupon_two, 200), Ok(200));
+            assert_eq!(
+                contract.create_campaign(String::from("Autumn"), 500, None, None, None),
+                Ok(0)
+            );
+            assert!(contract
+                .add_campaign_coupons(0, vec![(coupon_three, 100)], None, KeyScheme::Sr25519)
+                .is_ok());
+            assert_eq!(contract.available_balance(), 400);
+
+            // revoked coupon is released once
+            assert_eq!(
+                contract.burn_coupons(vec![coupon_two, coupon_two]),
+                Ok(CouponsResult {
+                    accepted: vec![coupon_two],
+                    declined: vec![coupon_two],
+                })
+            );
+            assert_eq!(contract.available_balance(), 600);
+
+            // redeemed coupon can't be burned
+            set_sender(accounts.eve);
+            let context = signing_context(contract_id().as_ref());
+            let signature = coupon_signer.sign(context.bytes(accounts.eve.as_ref()));
+
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
+                Ok(true)
+            );
+
+            set_sender(accounts.alice);
+            assert_eq!(
+                contract.burn_coupons(vec![coupon_one]),
+                Ok(CouponsResult {
+                    accepted: vec![],
+                    declined: vec![coupon_one],
+                })
+            );
+            assert_eq!(contract.available_balance(), 600);
+
+            // campaign reservation isn't released twice
+            assert!(contract.burn_coupons(vec![coupon_three]).is_ok());
+            assert_eq!(contract.close_campaign(0), Ok(0));
+
+            let campaign = contract.campaign(0).unwrap();
+            assert_eq!((campaign.burned, campaign.reserved), (100, 0));
+            assert_eq!(contract.available_balance(), 700);
+
+            assert_eq!(
+                contract.reconcile(None, 0, 0, None),
+                Ok(Reconciliation {
+                    token: None,
+                    reserved: 0,
+                    balance: 700,
+                    shortfall: 0,
+                    solvent: true,
+                    outstanding: 0,
+                    cursor: None,
+                    coupons: 3,
+                    consistent: Some(true),
+                })
+            );
+
+            // shortfall of the reserved funds is reported
+            assert_eq!(contract.add_coupon(accounts.bob, 500), Ok(500));
+            set_balance(contract_id(), 400);
+            assert_eq!(contract.available_balance(), 0);
+            assert_eq!(
+                contract.reconcile(None, 0, 0, None),
+                Ok(Reconciliation {
+                    token: None,
+                    reserved: 500,
+                    balance: 400,
+                    shortfall: 100,
+                    solvent: false,
+                    outstanding: 500,
+                    cursor: None,
+                    coupons: 4,
+                    consistent: Some(true),
+                })
+            );
+
+            // outstanding amount is recomputed by pages
+            assert_eq!(contract.set_max_batch_size(3), Ok(true));
+
+            let page = contract.reconcile(None, 0, 0, None).unwrap();
+            assert_eq!(
+                (page.outstanding, page.cursor, page.consistent),
+                (0, Some(3), None)
+            );
+
+            let page = contract
+                .reconcile(None, 3, page.outstanding, Some(page.coupons))
+                .unwrap();
+            assert_eq!(
+                (page.outstanding, page.cursor, page.consistent),
+                (500, None, Some(true))
+            );
+
+            // corrupted reserved counter is detected
+            contract.reserved = 600;
+
+            let page = contract.reconcile(None, 0, 0, None).unwrap();
+            let page = contract
+                .reconcile(None, 3, page.outstanding, Some(page.coupons))
+                .unwrap();
+            assert_eq!(
+                (page.reserved, page.outstanding, page.consistent),
+                (600, 500, Some(false))
+            );
+
+            // coupons registered between pages don't shift positions of airdrops
+            contract.reserved = 500;
+            set_balance(contract_id(), 1000);
+            assert_eq!(contract.add_airdrop([0x01; 32], 100, None, None), Ok(0));
+            assert_eq!(contract.add_airdrop([0x02; 32], 100, None, None), Ok(1));
+            assert_eq!(contract.set_max_batch_size(5), Ok(true));
+
+            let page = contract.reconcile(None, 0, 0, None).unwrap();
+            assert_eq!((page.outstanding, page.cursor, page.coupons), (600, Some(5), 4));
+
+            assert_eq!(contract.add_coupon(accounts.charlie, 100), Ok(100));
+            let page = contract
+                .reconcile(None, 5, page.outstanding, Some(page.coupons))
+                .unwrap();
+            assert_eq!(
+                (page.reserved, page.outstanding, page.cursor, page.consistent),
+                (800, 700, None, Some(false))
+            );
+
+            set_sender(accounts.eve);
+            assert_eq!(
+                contract.reconcile(None, 0, 0, None),
+                Err(Error::MissingRole(Role::Admin))
+            );
+        }
+
+        #[ink::test]
+        fn check_transfer_ownership() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            // setup sender, (by default `alice` also publisher and can add coupons)
+            set_sender(accounts.alice);
+            assert_eq!(contract.owner, accounts.alice);
 
-        /// Verification that the coupon is registered and it's value
-        #[ink(message)]
-        pub fn check_coupon(&self, coupon: CouponId) -> (bool, Balance) {
-            self.coupons
-                .get(&coupon)
-                .and_then(|exists_amount| Some((Self::env().balance() >= exists_amount, exists_amount)))
-                .and_then(|(enough_funds, exists_amount)| {
-                    Some((enough_funds && self.burned.get(&coupon).is_none(), exists_amount))
-                })
-                .unwrap_or_else(|| (false, 0))
-        }
+            // Transfer ownership to bob
+            assert_eq!(contract.transfer_ownership(accounts.bob), Ok(true));
+            assert_eq!(contract.owner, accounts.bob);
 
-        /// Get info on spare funds of the contract (not reserved for coupons)
-        /// available for withdrawal
-        /// Allow request only from the contract owner, otherwise return zero
-        #[ink(message)]
-        pub fn available_balance(&mut self) -> Balance {
-            (Self::env().caller() == self.owner)
-                .then(|| self.rest_balance())
-                .unwrap_or_default()
-        }
+            // try payback rest funds from old owner
+            assert_eq!(
+                contract.payback_not_reserved_funds(),
+                Err(Error::MissingRole(Role::Treasurer))
+            );
 
-        /// Transfer contract ownership to another user
-        #[ink(message)]
-        pub fn transfer_ownership(&mut self, account: AccountId) -> Result<bool, Error> {
-            (Self::env().caller() == self.owner)
-                .then(|| {
-                    self.owner = account;
-                    true
-                })
-                .ok_or(Error::AccessOwner)
+            // try payback rest funds from new owner
+            set_balance(accounts.bob, 0);
+            set_sender(accounts.bob);
+
+            assert_eq!(contract.payback_not_reserved_funds(), Ok(true));
+            assert_eq!(get_balance(accounts.bob), 1000);
+            assert_eq!(contract.available_balance(), 0);
         }
 
-        #[inline]
-        fn insert_coupon(&mut self, coupon: &CouponId, amount: Balance) -> Result<Balance, Error> {
-            self.coupons
-                .get(&coupon)
-                .is_none()
-                .then(|| true)
-                .ok_or(Error::CouponAlreadyExists)
-                .and_then(|_| {
-                    // insert new coupon to the storage
-                    self.coupons.insert(coupon, &amount);
-                    // reserve balance for payout
-                    self.reserved += amount;
+        #[ink::test]
+        fn check_roles() {
+            let accounts = default_accounts();
 
-                    Ok(amount)
-                })
-        }
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
 
-        #[inline]
-        fn burn_coupon(&mut self, coupon: &CouponId) -> Result<bool, Error> {
-            self.coupons
-                .get(&coupon)
-                .ok_or(Error::CouponNotFound)
-                .and_then(|amount| {
-                    // mark coupon as burned
-                    self.burned.insert(&coupon, &true);
-                    // cancellation of funds reservation
-                    self.reserved -= amount;
+            // owner has all roles
+            assert!(contract.has_role(Role::Admin, accounts.alice));
+            assert!(!contract.has_role(Role::Issuer, accounts.bob));
 
-                    Ok(true)
-                })
-        }
+            // grant roles from the owner
+            assert_eq!(contract.grant_role(Role::Issuer, accounts.bob), Ok(true));
+            assert_eq!(contract.grant_role(Role::Revoker, accounts.charlie), Ok(true));
+            assert_eq!(contract.grant_role(Role::Admin, accounts.django), Ok(true));
+            assert!(contract.has_role(Role::Issuer, accounts.bob));
 
-        #[inline]
-        fn rest_balance(&self) -> Balance {
-            Self::env().balance() - self.reserved
-        }
-    }
+            // issuer can add coupons only
+            set_sender(accounts.bob);
+            let (coupon_one, _) = get_coupon();
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
+            assert_eq!(contract.add_coupon(coupon_one, 300), Ok(300));
+            assert_eq!(
+                contract.burn_coupons(vec![coupon_one]),
+                Err(Error::MissingRole(Role::Revoker))
+            );
+            assert_eq!(
+                contract.grant_role(Role::Revoker, accounts.bob),
+                Err(Error::MissingRole(Role::Admin))
+            );
 
-        use schnorrkel::{Keypair, MiniSecretKey};
-        use AccountId;
+            // revoker can burn coupons only
+            set_sender(accounts.charlie);
+            assert!(contract.burn_coupons(vec![coupon_one]).is_ok());
+            assert_eq!(
+                contract.payback_not_reserved_funds(),
+                Err(Error::MissingRole(Role::Treasurer))
+            );
+            assert_eq!(contract.available_balance(), 0);
+
+            // admin manages roles
+            set_sender(accounts.django);
+            assert_eq!(contract.revoke_role(Role::Issuer, accounts.bob), Ok(true));
+            assert!(!contract.has_role(Role::Issuer, accounts.bob));
+
+            // only held roles are revoked, roles of the owner can't be revoked
+            assert_eq!(contract.revoke_role(Role::Issuer, accounts.bob), Ok(false));
+            assert_eq!(
+                contract.revoke_role(Role::Admin, accounts.alice),
+                Err(Error::OwnerRoleNotRevocable)
+            );
+            assert!(contract.has_role(Role::Admin, accounts.alice));
+
+            let raw_events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            let revoked = decode_events(&raw_events)
+                .into_iter()
+                .filter_map(|event| match event {
+                    Event::RoleRevoked(RoleRevoked { account, role }) => Some((account, role)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(revoked, vec![(accounts.bob, Role::Issuer)]);
+
+            set_sender(accounts.bob);
+            assert_eq!(
+                contract.add_coupon(accounts.eve, 100),
+                Err(Error::MissingRole(Role::Issuer))
+            );
+        }
 
         #[ink::test]
-        // Simple check for coupon insertion
-        // And coupon activation with it's secret key
-        fn insert_coupon_activation() {
+        fn pause_issuance_and_redemption() {
             let accounts = default_accounts();
 
             // setup contract
             let contract_balance = 1000;
             let mut contract = create_contract(contract_balance);
 
-            // setup sender (by default `alice` also publisher and can add coupons)
-            set_sender(accounts.alice);
-
-            // setup coupon
             let (coupon_one, coupon_signer) = get_coupon();
-            let coupon_amount: u128 = 500;
+            assert_eq!(contract.add_coupon(coupon_one, 300), Ok(300));
 
-            // adding one coupon with target amount
+            // only guardian can pause
+            set_sender(accounts.bob);
             assert_eq!(
-                contract.add_coupon(coupon_one.clone(), coupon_amount),
-                Ok(coupon_amount)
+                contract.set_paused(true, true),
+                Err(Error::MissingRole(Role::Guardian))
             );
 
-            // Check funds are reserved
             set_sender(accounts.alice);
-            assert_eq!(contract.available_balance(), 500);
+            assert_eq!(contract.grant_role(Role::Guardian, accounts.bob), Ok(true));
 
-            // Check methods by client
-            set_sender(accounts.eve);
-            set_balance(accounts.eve, 0);
+            set_sender(accounts.bob);
+            assert_eq!(contract.set_paused(true, true), Ok(true));
+            assert_eq!(contract.paused(), (true, true));
 
-            // check added coupon & amount;
+            // issuance & redemption are stopped
+            set_sender(accounts.alice);
+            assert_eq!(contract.add_coupon(accounts.charlie, 100), Err(Error::Paused));
             assert_eq!(
-                contract.check_coupon(coupon_one.clone()),
-                (true, coupon_amount)
+                contract.create_campaign(String::from("Winter"), 100, None, None, None),
+                Err(Error::Paused)
             );
 
-            // Activate coupon
+            set_sender(accounts.eve);
             let context = signing_context(contract_id().as_ref());
             let signature = coupon_signer.sign(context.bytes(accounts.eve.as_ref()));
 
             assert_eq!(
-                contract.activate_coupon(accounts.eve, coupon_one.clone(), signature.to_bytes()),
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
+                Err(Error::Paused)
+            );
+
+            // withdrawals keep working
+            set_sender(accounts.alice);
+            assert_eq!(contract.payback_not_reserved_funds(), Ok(true));
+
+            // resume redemption only
+            set_sender(accounts.bob);
+            assert_eq!(contract.set_paused(true, false), Ok(true));
+
+            set_sender(accounts.eve);
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
                 Ok(true)
             );
+        }
 
-            // Coupon activated - amount transfered to caller
-            assert_eq!(get_balance(accounts.eve), 500);
+        #[ink::test]
+        fn migrate_storage() {
+            let accounts = default_accounts();
 
-            // Check available funds (not reserved) and withdraw
+            // setup contract
+            let contract_balance = 1000;
+            let contract = create_contract(contract_balance);
+            let root_key = <Ocex as StorageKey>::KEY;
+
+            // new contract is on the current version, the root keeps the version 0 layout
+            let mut root = Vec::new();
+            ink::storage::traits::Storable::encode(&contract, &mut root);
+
+            assert_eq!(root, (accounts.alice, 0 as Balance).encode());
+            assert_eq!(contract.storage_version(), STORAGE_VERSION);
+
+            // contract of version 0 with active & burned coupons
+            let (coupon_one, coupon_signer) = get_coupon();
+            let (coupon_two, _) = get_coupon();
+
+            ink::env::clear_contract_storage(&contract.version.key());
+            ink::env::set_contract_storage(&root_key, &(accounts.alice, 300 as Balance));
+            ink::env::set_contract_storage(&(contract.coupons.key(), coupon_one), &(300 as Balance));
+            ink::env::set_contract_storage(&(contract.coupons.key(), coupon_two), &(200 as Balance));
+            ink::env::set_contract_storage(&(contract.burned.key(), coupon_two), &true);
+
+            let mut contract: Ocex = ink::env::get_contract_storage(&root_key).unwrap().unwrap();
+            assert_eq!(contract.storage_version(), 0);
+            assert_eq!(contract.coupon_counts().total, 0);
+
+            // only owner can upgrade & migrate
+            set_sender(accounts.bob);
+            assert_eq!(contract.upgrade([0; 32]), Err(Error::AccessOwner));
+            assert_eq!(contract.migrate(vec![]), Err(Error::AccessOwner));
+
+            // coupons are converted in batches
             set_sender(accounts.alice);
-            assert_eq!(contract.available_balance(), 500);
+            assert_eq!(contract.migrate(vec![coupon_one, coupon_two]), Ok(0));
+            assert_eq!(contract.migrate(vec![coupon_two]), Ok(0));
 
-            // transfer the rest of funds back to the owner
-            assert_eq!(contract.payback_not_reserved_funds(), Ok(true));
+            let counts = contract.coupon_counts();
+            assert_eq!((counts.total, counts.active, counts.redeemed), (2, 1, 1));
+
+            let info = contract.coupon_info(coupon_two);
+            assert_eq!((info.status, info.amount), (CouponStatus::Redeemed, 0));
+
+            // empty batch completes the migration
+            assert_eq!(contract.migrate(vec![]), Ok(STORAGE_VERSION));
+            assert_eq!(contract.storage_version(), STORAGE_VERSION);
+            assert_eq!(contract.migrate(vec![]), Err(Error::AlreadyMigrated));
+
+            // migrated coupon is redeemed from the reservation of version 0
+            set_sender(accounts.eve);
+            let context = signing_context(contract_id().as_ref());
+            let signature = coupon_signer
+                .sign(context.bytes(accounts.eve.as_ref()))
+                .to_bytes();
+
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature),
+                Ok(true)
+            );
+
+            set_sender(accounts.alice);
+            assert_eq!(contract.available_balance(), 700);
         }
 
         #[ink::test]
-        fn insert_coupons() {
+        fn emit_coupon_events() {
             let accounts = default_accounts();
 
             // setup contract
             let contract_balance = 1000;
             let mut contract = create_contract(contract_balance);
 
-            // setup sender, (by default `alice` also publisher and can add coupons)
+            // setup coupons
+            let (coupon_one, coupon_signer) = get_coupon();
+            let (coupon_two, _) = get_coupon();
+
+            assert_eq!(contract.add_coupon(coupon_one, 300), Ok(300));
+            assert_eq!(contract.add_coupon(coupon_two, 200), Ok(200));
+
+            // burn second coupon
+            assert!(contract.burn_coupons(vec![coupon_two]).is_ok());
+
+            // activate first coupon from client
+            set_sender(accounts.eve);
+            let context = signing_context(contract_id().as_ref());
+            let signature = coupon_signer.sign(context.bytes(accounts.eve.as_ref()));
+
+            assert_eq!(
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
+                Ok(true)
+            );
+
+            // withdraw spare funds & pass ownership
             set_sender(accounts.alice);
+            assert_eq!(contract.payback_not_reserved_funds(), Ok(true));
+            assert_eq!(contract.transfer_ownership(accounts.bob), Ok(true));
 
-            // setup coupon
-            let (coupon_one, _) = get_coupon();
-            let coupon_amount: u128 = 500;
+            let raw_events = ink::env::test::recorded_events().collect::<Vec<_>>();
+            let events = decode_events(&raw_events);
+            assert_eq!(events.len(), 6);
 
-            let test_coupons = [
-                Some(coupon_one.clone()),
-                Some(accounts.charlie),
-                Some(accounts.django),
-                Some(accounts.frank),
-                Some(accounts.bob),
-            ];
+            assert!(matches!(
+                events[0],
+                Event::CouponAdded(CouponAdded { coupon, amount: 300, token: None }) if coupon == coupon_one
+            ));
+            assert!(matches!(
+                events[1],
+                Event::CouponAdded(CouponAdded { coupon, amount: 200, token: None }) if coupon == coupon_two
+            ));
+            assert!(matches!(
+                events[2],
+                Event::CouponBurned(CouponBurned { coupon, amount: 200 }) if coupon == coupon_two
+            ));
+            assert!(matches!(
+                events[3],
+                Event::CouponActivated(CouponActivated { coupon, receiver, amount: 300 })
+                    if coupon == coupon_one && receiver == accounts.eve
+            ));
+            assert!(matches!(
+                events[4],
+                Event::FundsWithdrawn(FundsWithdrawn { receiver, amount: 700, token: None })
+                    if receiver == accounts.alice
+            ));
+            assert!(matches!(
+                events[5],
+                Event::OwnershipTransferred(OwnershipTransferred { previous, owner })
+                    if previous == accounts.alice && owner == accounts.bob
+            ));
 
-            // insert multiple coupons with total amount
-            // that exceeds the contract spare liquidity
+            // coupon id & receiver are indexed as topics
+            assert_eq!(raw_events[3].topics.len(), 3);
+        }
+
+        #[ink::test]
+        fn expired_coupons() {
+            let accounts = default_accounts();
+
+            // setup contract
+            let contract_balance = 1000;
+            let mut contract = create_contract(contract_balance);
+
+            // setup coupons
+            let (coupon_one, coupon_signer) = get_coupon();
+            let (coupon_two, _) = get_coupon();
+            let expires = Some(Expiry::BlockNumber(2));
+
+            assert_eq!(
+                contract.add_coupon_with_options(coupon_one, 300, expires, None, KeyScheme::Sr25519),
+                Ok(300)
+            );
+            assert_eq!(contract.add_coupon(coupon_two, 200), Ok(200));
+            assert_eq!(contract.check_coupon(coupon_one), (true, 300));
+            assert_eq!(contract.coupon_info(coupon_one).expires, expires);
+
+            // not expired coupons can't be reclaimed
             assert_eq!(
-                contract.add_coupons(test_coupons, coupon_amount),
+                contract.reclaim_expired_coupons(vec![coupon_one]),
                 Ok(CouponsResult {
-                    accepted: [Some(coupon_one.clone()), Some(accounts.charlie), None, None, None,],
-                    declined: [Some(accounts.django), Some(accounts.frank), Some(accounts.bob), None, None,]
+                    accepted: vec![],
+                    declined: vec![coupon_one],
                 })
             );
 
-            // Check that funds are reserved and withdrawal is restricted
-            assert_eq!(contract.available_balance(), 0);
+            advance_blocks(2);
+            assert_eq!(contract.check_coupon(coupon_one), (false, 300));
+
+            // coupons can't be issued already expired
+            assert_eq!(
+                contract.add_coupon_with_options(
+                    accounts.charlie,
+                    100,
+                    Some(Expiry::BlockNumber(1)),
+                    None,
+                    KeyScheme::Sr25519
+                ),
+                Err(Error::CouponExpired)
+            );
+
+            // activation of expired coupon is rejected
+            set_sender(accounts.eve);
+            let context = signing_context(contract_id().as_ref());
+            let signature = coupon_signer.sign(context.bytes(accounts.eve.as_ref()));
 
-            // Burn inserted coupons
             assert_eq!(
-                contract.burn_coupons([Some(coupon_one.clone()), Some(accounts.charlie), None, None, None]),
+                contract.activate_coupon(accounts.eve, coupon_one, signature.to_bytes()),
+                Err(Error::CouponExpired)
+            );
+
+            // owner reclaims reservation of expired coupon only
+            set_sender(accounts.alice);
+            assert_eq!(
+                contract.reclaim_expired_coupons(vec![coupon_one, coupon_two]),
                 Ok(CouponsResult {
-                    accepted: [Some(coupon_one.clone()), Some(accounts.charlie), None, None, None,],
-                    declined: [None, None, None, None, None]
+                    accepted: vec![coupon_one],
+                    declined: vec![coupon_two],
                 })
             );
 
-            assert_eq!(contract.available_balance(), 1000);
+            assert_eq!(contract.available_balance(), 800);
+
+            // reclaimed coupon is reported apart from expired ones
+            assert_eq!(contract.coupon_info(coupon_one).status, CouponStatus::Reclaimed);
+            assert_eq!(
+                contract.list_coupons(0, 10, Some(CouponStatus::Reclaimed)),
+                Ok((vec![coupon_one], None))
+            );
+            assert_eq!(contract.coupon_counts().reclaimed, 1);
         }
 
         #[ink::test]
-        fn check_transfer_ownership() {
+        fn partial_redemption() {
             let accounts = default_accounts();
 
             // setup contract
             let contract_balance = 1000;
             let mut contract = create_contract(contract_balance);
 
-            // setup sender, (by default `alice` also publisher and can add coupons)
+            // setup coupon
+            let (coupon_one, coupon_signer) = get_coupon();
+            assert_eq!(contract.add_coupon(coupon_one, 500), Ok(500));
+
+            set_sender(accounts.eve);
+            set_balance(accounts.eve, 0);
+
+            let context = signing_context(contract_id().as_ref());
+            let sign = |amount: Balance, nonce: u32, deadline: BlockNumber| {
+                let payload = RedemptionPayload {
+                    domain: payload_domain(PayloadAction::Redemption, Hash::default()),
+                    coupon: coupon_one,
+                    receiver: accounts.eve,
+                    amount,
+                    nonce,
+                    deadline,
+                };
+
+                coupon_signer.sign(context.bytes(&payload.encode())).to_bytes()
+            };
+
+            // spend part of the coupon balance
+            assert_eq!(contract.coupon_nonce(coupon_one), 0);
+            assert_eq!(
+                contract.redeem_coupon(accounts.eve, coupon_one, 200, 5, sign(200, 0, 5)),
+                Ok(200)
+            );
+            assert_eq!(contract.check_coupon(coupon_one), (true, 300));
+            assert_eq!(get_balance(accounts.eve), 200);
+
+            // the same claim can't be replayed
+            assert_eq!(
+                contract.redeem_coupon(accounts.eve, coupon_one, 200, 5, sign(200, 0, 5)),
+                Err(Error::VerifySignatureFailed)
+            );
+
+            // signed amount can't be changed
+            assert_eq!(
+                contract.redeem_coupon(accounts.eve, coupon_one, 300, 5, sign(200, 1, 5)),
+                Err(Error::VerifySignatureFailed)
+            );
+
+            // expired signature is rejected
+            advance_blocks(2);
+            assert_eq!(
+                contract.redeem_coupon(accounts.eve, coupon_one, 400, 1, sign(400, 1, 1)),
+                Err(Error::SignatureExpired)
+            );
+
+            // payout is limited by the rest of the coupon balance
+            assert_eq!(
+                contract.redeem_coupon(accounts.eve, coupon_one, 400, 5, sign(400, 1, 5)),
+                Ok(300)
+            );
+            assert_eq!(contract.check_coupon(coupon_one), (false, 500));
+            assert_eq!(get_balance(accounts.eve), 500);
+
+            assert_eq!(
+                contract.redeem_coupon(accounts.eve, coupon_one, 100, 5, sign(100, 2, 5)),
+                Err(Error::CouponAlreadyRedeemed)
+            );
+
             set_sender(accounts.alice);
-            assert_eq!(contract.owner, accounts.alice);
-            
-            // Transfer ownership to bob
-            assert_eq!(contract.transfer_ownership(accounts.bob), Ok(true));
-            assert_eq!(contract.owner, accounts.bob);
+            assert_eq!(contract.available_balance(), 500);
+        }
 
-            // try payback rest funds from old owner
-            assert_eq!(contract.payback_not_reserved_funds(), Err(Error::AccessOwner));
+        type Event = <Ocex as ::ink::reflect::ContractEventBase>::Type;
 
-            // try payback rest funds from new owner
-            set_balance(accounts.bob, 0);
-            set_sender(accounts.bob);
-    
-            assert_eq!(contract.payback_not_reserved_funds(), Ok(true));
-            assert_eq!(get_balance(accounts.bob), 1000);
-            assert_eq!(contract.available_balance(), 0);
+        fn decode_events(events: &[ink::env::test::EmittedEvent]) -> Vec<Event> {
+            events
+                .iter()
+                .map(|event| {
+                    <Event as scale::Decode>::decode(&mut &event.data[..]).expect("Invalid event data")
+                })
+                .collect()
         }
 
         fn create_contract(initial_balance: Balance) -> Ocex {
@@ -484,6 +5708,15 @@ mod ocex {
             ink::env::test::callee::<ink::env::DefaultEnvironment>()
         }
 
+        fn payload_domain(action: PayloadAction, chain: Hash) -> PayloadDomain {
+            PayloadDomain {
+                version: 1,
+                action,
+                contract: contract_id(),
+                chain,
+            }
+        }
+
         fn set_sender(sender: AccountId) {
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(sender);
         }
@@ -492,6 +5725,16 @@ mod ocex {
             ink::env::test::default_accounts::<ink::env::DefaultEnvironment>()
         }
 
+        fn advance_blocks(count: u32) {
+            for _ in 0..count {
+                ink::env::test::advance_block::<ink::env::DefaultEnvironment>();
+            }
+        }
+
+        fn set_block_timestamp(timestamp: Timestamp) {
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(timestamp);
+        }
+
         fn set_balance(account_id: AccountId, balance: Balance) {
             ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(account_id, balance)
         }
@@ -501,10 +5744,21 @@ mod ocex {
                 .expect("Cannot get account balance")
         }
 
+        fn signature_bytes(signature: &[u8]) -> [u8; 64] {
+            // ECDSA recovery id is dropped
+            signature[..64].try_into().unwrap()
+        }
+
+        fn eth_sign(pair: &ecdsa::Pair, message: &[u8]) -> ecdsa::Signature {
+            // EIP-191 personal message hashed with keccak256
+            let prefix = format!("\x19Ethereum Signed Message:\n{}", message.len());
+            pair.sign_prehashed(&keccak_256(&[prefix.as_bytes(), message].concat()))
+        }
+
         fn get_coupon() -> (CouponId, Keypair) {
             let coupon = MiniSecretKey::generate();
             let keypair: Keypair = Keypair::from(coupon.expand(MiniSecretKey::ED25519_MODE));
-            let coupon: CouponId = keypair.secret.to_public().to_bytes().clone().into();
+            let coupon: CouponId = keypair.secret.to_public().to_bytes().into();
 
             (coupon, keypair)
         }